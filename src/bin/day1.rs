@@ -1,30 +1,24 @@
 use anyhow::Result;
+use itertools::process_results;
 
-use aoc2021::util::input_lines;
+use aoc2021::{sonar::count_window_increases, util::input_lines};
 
 fn main() -> Result<()> {
-    let depths = parse_input()?;
-
-    let depth_increases = (1..depths.len())
-        .filter(|&index| depths[index] > depths[index - 1])
-        .count();
+    let depth_increases = count_depth_window_increases(1)?;
     dbg!(depth_increases);
 
-    // A B C D E F G
-    // A <-> D
-    // B <-> E
-    // C <-> F
-
-    let window_sum_increases = (3..depths.len())
-        .filter(|&index| depths[index] > depths[index - 3])
-        .count();
+    let window_sum_increases = count_depth_window_increases(3)?;
     dbg!(window_sum_increases);
 
     Ok(())
 }
 
-fn parse_input() -> Result<Vec<u32>> {
-    let result: Result<Vec<_>> = input_lines()?.map(|line| Ok(line?.parse()?)).collect();
-    let result = result?;
-    Ok(result)
+fn count_depth_window_increases(window: usize) -> Result<usize> {
+    process_results(parse_input()?, |depths| {
+        count_window_increases(depths, window)
+    })
+}
+
+fn parse_input() -> Result<impl Iterator<Item = Result<u32>>> {
+    Ok(input_lines()?.map(|line| Ok(line?.parse()?)))
 }
@@ -1,117 +1,406 @@
-use anyhow::Result;
+use std::collections::HashMap;
 
-use aoc2021::util::input_lines;
-
-const PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+use anyhow::{bail, Context, Result};
 
-const ILLEGAL_CHAR_SCORES: [(char, u32); 4] = [(')', 3), (']', 57), ('}', 1197), ('>', 25137)];
-
-const MISSING_CHAR_SCORES: [(char, u32); 4] = [(')', 1), (']', 2), ('}', 3), ('>', 4)];
+use aoc2021::util::input_lines;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum LineCondition {
     Valid,
     Invalid { index: usize, char: char },
+    UnknownChar { index: usize, char: char },
     Incomplete { missing: String },
 }
 
-fn main() -> Result<()> {
-    let lines = parse_input()?;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Edit {
+    /// Insert `char` before the character at `index` of the original line.
+    Insert {
+        index: usize,
+        char: char,
+    },
+    Delete {
+        index: usize,
+        char: char,
+    },
+}
 
-    let score: u32 = lines
-        .iter()
-        .filter_map(|line| {
-            if let LineCondition::Invalid { char, .. } = validate_line(line) {
-                Some(illegal_char_score(char).unwrap())
-            } else {
-                None
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BracketSyntax {
+    close_for_open: HashMap<char, char>,
+    open_for_close: HashMap<char, char>,
+    illegal_char_scores: HashMap<char, u64>,
+    missing_char_scores: HashMap<char, u64>,
+    completion_base: u64,
+}
+
+impl BracketSyntax {
+    fn new(pairs: &[(char, char)]) -> Result<Self> {
+        let mut syntax = Self {
+            close_for_open: HashMap::new(),
+            open_for_close: HashMap::new(),
+            illegal_char_scores: HashMap::new(),
+            missing_char_scores: HashMap::new(),
+            completion_base: 5,
+        };
+
+        for &(open, close) in pairs {
+            if syntax.is_bracket(open) || syntax.is_bracket(close) || open == close {
+                bail!("Bracket pair {}{} overlaps another pair", open, close);
             }
-        })
-        .sum();
-    dbg!(score);
 
-    let mut completion_scores: Vec<_> = lines
-        .iter()
-        .filter_map(|line| match validate_line(line) {
-            LineCondition::Incomplete { missing } => Some(completion_score(&missing)),
-            _ => None,
-        })
-        .collect();
-    let middle_index = (completion_scores.len() - 1) / 2;
-    let (_, &mut winner, _) = completion_scores.select_nth_unstable(middle_index);
-    dbg!(winner);
+            syntax.close_for_open.insert(open, close);
+            syntax.open_for_close.insert(close, open);
+        }
 
-    Ok(())
-}
+        Ok(syntax)
+    }
 
-fn validate_line(line: &str) -> LineCondition {
-    let mut stack = vec![];
+    fn standard() -> Self {
+        let syntax = Self::new(&[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')])
+            .and_then(|syntax| {
+                syntax.with_illegal_char_scores(&[(')', 3), (']', 57), ('}', 1197), ('>', 25137)])
+            })
+            .and_then(|syntax| {
+                syntax.with_missing_char_scores(&[(')', 1), (']', 2), ('}', 3), ('>', 4)])
+            })
+            .unwrap();
 
-    for (index, char) in line.char_indices() {
-        match char {
-            '(' | '[' | '{' | '<' => stack.push(char),
-            ')' | ']' | '}' | '>' => {
-                if let Some(last) = stack.pop() {
-                    if opening_char(char).unwrap() != last {
-                        return LineCondition::Invalid { index, char };
-                    }
-                } else {
+        syntax.with_completion_base(5)
+    }
+
+    fn check_closing_chars(&self, scores: &[(char, u64)]) -> Result<()> {
+        for &(char, _) in scores {
+            if !self.open_for_close.contains_key(&char) {
+                bail!("Scored character '{}' is not a closing bracket", char);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the scores of the closing brackets that make a line invalid.
+    fn with_illegal_char_scores(mut self, scores: &[(char, u64)]) -> Result<Self> {
+        self.check_closing_chars(scores)?;
+        self.illegal_char_scores = scores.iter().copied().collect();
+        Ok(self)
+    }
+
+    /// Sets the scores of the closing brackets that complete an incomplete line.
+    fn with_missing_char_scores(mut self, scores: &[(char, u64)]) -> Result<Self> {
+        self.check_closing_chars(scores)?;
+        self.missing_char_scores = scores.iter().copied().collect();
+        Ok(self)
+    }
+
+    /// Sets the factor that the completion score is multiplied by before each character.
+    fn with_completion_base(mut self, base: u64) -> Self {
+        self.completion_base = base;
+        self
+    }
+
+    fn is_bracket(&self, char: char) -> bool {
+        self.close_for_open.contains_key(&char) || self.open_for_close.contains_key(&char)
+    }
+
+    /// Validates a line in a single pass, keeping only the currently open brackets in memory.
+    fn validate(&self, line: impl IntoIterator<Item = char>) -> LineCondition {
+        let mut stack = vec![];
+
+        for (index, char) in line.into_iter().enumerate() {
+            if let Some(&close) = self.close_for_open.get(&char) {
+                stack.push(close);
+            } else if self.open_for_close.contains_key(&char) {
+                if stack.pop() != Some(char) {
                     return LineCondition::Invalid { index, char };
                 }
+            } else {
+                return LineCondition::UnknownChar { index, char };
             }
-            _ => {}
+        }
+
+        if stack.is_empty() {
+            return LineCondition::Valid;
+        }
+
+        LineCondition::Incomplete {
+            missing: stack.into_iter().rev().collect(),
         }
     }
 
-    if stack.is_empty() {
-        return LineCondition::Valid;
+    fn illegal_char_score(&self, char: char) -> Option<u64> {
+        self.illegal_char_scores.get(&char).copied()
     }
 
-    LineCondition::Incomplete {
-        missing: stack
-            .into_iter()
-            .rev()
-            .map(closing_char)
-            .collect::<Option<String>>()
-            .unwrap(),
+    fn completion_score(&self, missing: &str) -> Option<u64> {
+        missing.chars().try_fold(0, |acc, char| {
+            Some(acc * self.completion_base + self.missing_char_scores.get(&char)?)
+        })
     }
-}
 
-fn opening_char(closing_char: char) -> Option<char> {
-    PAIRS
-        .iter()
-        .find(|(_, close)| *close == closing_char)
-        .map(|(open, _)| *open)
-}
+    /// Finds a smallest set of insertions and deletions that makes the line valid.
+    /// Brackets without a partner get one inserted, and unknown characters are deleted.
+    /// Runs in cubic time and quadratic memory in the length of the line.
+    fn repair(&self, line: &str) -> Vec<Edit> {
+        let chars: Vec<char> = line.chars().collect();
+        let len = chars.len();
 
-fn closing_char(opening_char: char) -> Option<char> {
-    PAIRS
-        .iter()
-        .find(|(open, _)| *open == opening_char)
-        .map(|(_, close)| *close)
-}
+        // cost[start][end] is the number of edits needed to fix chars[start..end],
+        // and partner[start][end] is the index that chars[start] is matched with, if any.
+        let mut cost = vec![vec![0usize; len + 1]; len + 1];
+        let mut partner = vec![vec![None; len + 1]; len + 1];
+
+        for start in (0..len).rev() {
+            for end in start + 1..=len {
+                cost[start][end] = 1 + cost[start + 1][end];
+
+                let close = match self.close_for_open.get(&chars[start]) {
+                    Some(&close) => close,
+                    None => continue,
+                };
+
+                for middle in start + 1..end {
+                    if chars[middle] != close {
+                        continue;
+                    }
+
+                    let candidate = cost[start + 1][middle] + cost[middle + 1][end];
+                    if candidate < cost[start][end] {
+                        cost[start][end] = candidate;
+                        partner[start][end] = Some(middle);
+                    }
+                }
+            }
+        }
+
+        let mut edits = vec![];
+        let mut ranges = vec![(0, len)];
+        while let Some((start, end)) = ranges.pop() {
+            if start >= end {
+                continue;
+            }
 
-fn illegal_char_score(c: char) -> Option<u32> {
-    ILLEGAL_CHAR_SCORES
-        .iter()
-        .find(|(candidate, _)| *candidate == c)
-        .map(|(_, score)| *score)
+            let char = chars[start];
+            match partner[start][end] {
+                Some(middle) => {
+                    ranges.push((middle + 1, end));
+                    ranges.push((start + 1, middle));
+                }
+                None => {
+                    if let Some(&close) = self.close_for_open.get(&char) {
+                        edits.push(Edit::Insert {
+                            index: start + 1,
+                            char: close,
+                        });
+                    } else if let Some(&open) = self.open_for_close.get(&char) {
+                        edits.push(Edit::Insert {
+                            index: start,
+                            char: open,
+                        });
+                    } else {
+                        edits.push(Edit::Delete { index: start, char });
+                    }
+
+                    ranges.push((start + 1, end));
+                }
+            }
+        }
+
+        edits
+    }
 }
 
-fn completion_score(chars: &str) -> u64 {
-    chars
-        .chars()
-        .filter_map(missing_char_score)
-        .fold(0, |acc, score| acc * 5 + score as u64)
+fn apply_edits(line: &str, edits: &[Edit]) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut edits = edits.iter().peekable();
+    let mut result = String::new();
+
+    for index in 0..=chars.len() {
+        let mut keep = index < chars.len();
+
+        while let Some(&&edit) = edits.peek() {
+            match edit {
+                Edit::Insert {
+                    index: edit_index,
+                    char,
+                } if edit_index == index => result.push(char),
+                Edit::Delete {
+                    index: edit_index, ..
+                } if edit_index == index => keep = false,
+                _ => break,
+            }
+            edits.next();
+        }
+
+        if keep {
+            result.push(chars[index]);
+        }
+    }
+
+    result
 }
 
-fn missing_char_score(c: char) -> Option<u32> {
-    MISSING_CHAR_SCORES
-        .iter()
-        .find(|(candidate, _)| *candidate == c)
-        .map(|(_, score)| *score)
+fn main() -> Result<()> {
+    let lines = parse_input()?;
+    let syntax = BracketSyntax::standard();
+
+    let mut score = 0;
+    let mut completion_scores = vec![];
+    let mut repair_edits = 0;
+
+    for line in &lines {
+        match syntax.validate(line.chars()) {
+            LineCondition::Valid => {}
+            LineCondition::Invalid { char, .. } => {
+                score += syntax
+                    .illegal_char_score(char)
+                    .with_context(|| format!("No score for illegal '{}'", char))?;
+
+                let edits = syntax.repair(line);
+                let repaired = apply_edits(line, &edits);
+                if syntax.validate(repaired.chars()) != LineCondition::Valid {
+                    bail!("Repairing '{}' produced invalid '{}'", line, repaired);
+                }
+                repair_edits += edits.len();
+            }
+            LineCondition::UnknownChar { index, char } => {
+                bail!(
+                    "Unknown character '{}' at index {} of '{}'",
+                    char,
+                    index,
+                    line
+                )
+            }
+            LineCondition::Incomplete { missing } => completion_scores.push(
+                syntax
+                    .completion_score(&missing)
+                    .with_context(|| format!("No score for completion '{}'", missing))?,
+            ),
+        }
+    }
+    dbg!(score);
+
+    let middle_index = (completion_scores.len() - 1) / 2;
+    let (_, &mut winner, _) = completion_scores.select_nth_unstable(middle_index);
+    dbg!(winner);
+
+    dbg!(repair_edits);
+
+    Ok(())
 }
 
 fn parse_input() -> Result<Vec<String>> {
     input_lines()?.collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_edits, BracketSyntax, Edit, LineCondition};
+
+    #[test]
+    fn validate() {
+        let syntax = BracketSyntax::standard();
+
+        assert_eq!(syntax.validate("([]<>)".chars()), LineCondition::Valid);
+        assert_eq!(
+            syntax.validate("{([(<{}[<>[]}>{[]{[(<()>".chars()),
+            LineCondition::Invalid {
+                index: 12,
+                char: '}'
+            }
+        );
+        assert_eq!(
+            syntax.validate("[({(<(())[]>[[{[]{<()<>>".chars()),
+            LineCondition::Incomplete {
+                missing: "}}]])})]".to_owned()
+            }
+        );
+        assert_eq!(syntax.completion_score("}}]])})]"), Some(288957));
+        assert_eq!(
+            syntax.validate("(x)".chars()),
+            LineCondition::UnknownChar {
+                index: 1,
+                char: 'x'
+            }
+        );
+    }
+
+    #[test]
+    fn custom_pairs() {
+        let syntax = BracketSyntax::new(&[('a', 'b'), ('(', ')')]).unwrap();
+
+        assert_eq!(syntax.validate("a(ab)b".chars()), LineCondition::Valid);
+        assert_eq!(
+            syntax.validate("a(".chars()),
+            LineCondition::Incomplete {
+                missing: ")b".to_owned()
+            }
+        );
+        assert_eq!(syntax.completion_score(")b"), None);
+        assert!(BracketSyntax::new(&[('a', 'b'), ('b', 'c')]).is_err());
+    }
+
+    #[test]
+    fn custom_scores() {
+        let syntax = BracketSyntax::new(&[('a', 'b'), ('(', ')')])
+            .unwrap()
+            .with_illegal_char_scores(&[('b', 10), (')', 20)])
+            .unwrap()
+            .with_missing_char_scores(&[('b', 1), (')', 2)])
+            .unwrap()
+            .with_completion_base(3);
+
+        assert_eq!(
+            syntax.validate("a(b".chars()),
+            LineCondition::Invalid {
+                index: 2,
+                char: 'b'
+            }
+        );
+        assert_eq!(syntax.illegal_char_score('b'), Some(10));
+        assert_eq!(syntax.illegal_char_score('a'), None);
+        assert_eq!(syntax.completion_score(")b"), Some(2 * 3 + 1));
+
+        let syntax = BracketSyntax::new(&[('a', 'b')]).unwrap();
+        assert!(syntax
+            .clone()
+            .with_illegal_char_scores(&[('a', 1)])
+            .is_err());
+        assert!(syntax.with_missing_char_scores(&[(')', 1)]).is_err());
+    }
+
+    #[test]
+    fn repair() {
+        let syntax = BracketSyntax::standard();
+
+        let edits = syntax.repair("(]");
+        assert_eq!(
+            edits,
+            [
+                Edit::Insert {
+                    index: 1,
+                    char: ')'
+                },
+                Edit::Insert {
+                    index: 1,
+                    char: '['
+                },
+            ]
+        );
+        assert_eq!(apply_edits("(]", &edits), "()[]");
+
+        assert_eq!(apply_edits("([)]", &syntax.repair("([)]")), "()[()]");
+        assert_eq!(
+            syntax.repair("(x)"),
+            [Edit::Delete {
+                index: 1,
+                char: 'x'
+            }]
+        );
+        assert_eq!(syntax.repair("<{}>"), []);
+
+        for line in ["{([(<{}[<>[]}>{[]{[(<()>", "[[<[([]))<([[{}[[()]]]", ">>{{"] {
+            let repaired = apply_edits(line, &syntax.repair(line));
+            assert_eq!(syntax.validate(repaired.chars()), LineCondition::Valid);
+        }
+    }
+}
@@ -1,79 +1,149 @@
-use std::{collections::HashSet, fmt::Display};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
 use aoc2021::{
+    bitmap::BitGrid,
     matrix::{Matrix, Position},
     util::input_lines,
 };
 
-const GRID_ROWS: usize = 10;
-const GRID_COLS: usize = 10;
-
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct Grid {
     data: Matrix<u8>,
 }
 
 impl Grid {
-    fn step(&self) -> (Grid, usize) {
+    /// Advances the grid by one step, returning the new grid and which octopi flashed.
+    fn step(&self) -> (Grid, BitGrid) {
         let mut new_grid = self.clone();
+        let mut flashed = BitGrid::new(self.data.rows(), self.data.cols());
 
+        let mut pending = vec![];
         for point in new_grid.data.all_points() {
-            *new_grid.data.get_mut(&point) += 1;
+            let energy = new_grid.data.get_mut(&point);
+            *energy += 1;
+            if *energy > 9 {
+                pending.push(point);
+            }
         }
 
-        let mut flashed = HashSet::new();
-        let mut done = false;
-        while !done {
-            done = true;
+        // Every octopus is queued exactly once, when its energy first exceeds 9
+        while let Some(point) = pending.pop() {
+            flashed.set(&point, true);
 
-            for point in new_grid.data.all_points() {
-                if *new_grid.data.get(&point) <= 9 {
-                    continue;
+            for neighbour in new_grid.data.neighbours_with_diagonals(&point) {
+                let energy = new_grid.data.get_mut(&neighbour);
+                *energy += 1;
+                if *energy == 10 {
+                    pending.push(neighbour);
                 }
+            }
+        }
 
-                if !flashed.insert(point) {
-                    continue;
-                }
+        for point in flashed.iter_ones() {
+            *new_grid.data.get_mut(&point) = 0;
+        }
 
-                done = false;
+        (new_grid, flashed)
+    }
 
-                for neighbour in new_grid.data.neighbours_with_diagonals(&point) {
-                    *new_grid.data.get_mut(&neighbour) += 1;
-                }
+    /// Yields the grid and the flashes after every step.
+    fn history(&self) -> impl Iterator<Item = (Grid, BitGrid)> {
+        let mut current = self.clone();
+
+        std::iter::repeat_with(move || {
+            let (next, flashed) = current.step();
+            current = next.clone();
+            (next, flashed)
+        })
+    }
+
+    fn sync_point(&self) -> usize {
+        let octopi = self.data.rows() * self.data.cols();
+
+        self.history()
+            .position(|(_, flashed)| flashed.count_ones() == octopi)
+            .unwrap()
+            + 1
+    }
+
+    /// Steps the grid until a state repeats. Since every state determines the next
+    /// one, from then on the grid goes around the same cycle forever.
+    fn find_cycle(&self) -> Cycle {
+        let mut seen = HashMap::from([(self.clone(), 0)]);
+        let mut states = vec![self.clone()];
+        let mut flashes = vec![0];
+
+        for (next, flashed) in self.history() {
+            let step = states.len();
+            flashes.push(flashes[step - 1] + flashed.count_ones());
+
+            if let Some(&start) = seen.get(&next) {
+                return Cycle {
+                    start,
+                    period: step - start,
+                    states,
+                    flashes,
+                };
             }
+
+            seen.insert(next.clone(), step);
+            states.push(next);
         }
 
-        let total_flashes = flashed.len();
+        unreachable!()
+    }
+}
 
-        for point in flashed.into_iter() {
-            *new_grid.data.get_mut(&point) = 0;
+/// The states of a grid up to and including one full cycle, and the total number of
+/// flashes up to each of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Cycle {
+    start: usize,
+    period: usize,
+    states: Vec<Grid>,
+    flashes: Vec<usize>,
+}
+
+impl Cycle {
+    fn state_at(&self, step: u64) -> &Grid {
+        &self.states[self.reduce(step)]
+    }
+
+    fn total_flashes(&self, step: u64) -> u64 {
+        let reduced = self.reduce(step);
+        let flashes = self.flashes[reduced] as u64;
+
+        if reduced == step as usize {
+            return flashes;
         }
 
-        (new_grid, total_flashes)
+        let cycles = (step - reduced as u64) / self.period as u64;
+        let flashes_per_cycle =
+            (self.flashes[self.start + self.period] - self.flashes[self.start]) as u64;
+
+        flashes + cycles * flashes_per_cycle
     }
 
-    fn sync_point(&self) -> usize {
-        let mut current = self.clone();
-        for step in 1usize.. {
-            let (next, flashes) = current.step();
-            if flashes == GRID_ROWS * GRID_COLS {
-                return step;
-            }
-            current = next;
+    fn reduce(&self, step: u64) -> usize {
+        match usize::try_from(step) {
+            Ok(step) if step < self.start + self.period => step,
+            _ => self.start + ((step - self.start as u64) % self.period as u64) as usize,
         }
-        panic!("Solution not found")
     }
 }
 
 impl Display for Grid {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in 0..GRID_ROWS {
-            for col in 0..GRID_COLS {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in 0..self.data.rows() {
+            for col in 0..self.data.cols() {
                 write!(f, "{}", self.data.get(&Position { row, col }))?;
             }
-            write!(f, "\n")?;
+            writeln!(f)?;
         }
         Ok(())
     }
@@ -82,30 +152,114 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 fn main() -> Result<()> {
     let grid = parse_input()?;
 
-    let (_, total_flashes) = (0..100).fold((grid.clone(), 0), |acc, _| {
-        let (next, flashes) = acc.0.step();
-        (next, acc.1 + flashes)
-    });
+    let total_flashes: usize = grid
+        .history()
+        .take(100)
+        .map(|(_, flashed)| flashed.count_ones())
+        .sum();
     dbg!(total_flashes);
 
     let sync_point = grid.sync_point();
     dbg!(sync_point);
 
+    let cycle = grid.find_cycle();
+    let total_flashes_billion = cycle.total_flashes(1_000_000_000);
+    dbg!(total_flashes_billion);
+
+    print!("{}", cycle.state_at(1_000_000_000));
+
     Ok(())
 }
 
 fn parse_input() -> Result<Grid> {
-    let mut octopii = vec![];
+    let mut octopi = vec![];
+    let mut cols = None;
 
-    for line in input_lines()? {
+    for (index, line) in input_lines()?.enumerate() {
         let line = line?;
 
-        octopii.extend(line.chars().map(|c| c.to_digit(10).unwrap() as u8));
-        assert!(octopii.len() % GRID_COLS == 0);
+        let row: Option<Vec<u8>> = line
+            .chars()
+            .map(|c| c.to_digit(10).map(|digit| digit as u8))
+            .collect();
+        let row = row.with_context(|| format!("Invalid energy level in line {}", index + 1))?;
+
+        if *cols.get_or_insert(row.len()) != row.len() {
+            bail!("Line {} has a different length", index + 1);
+        }
+
+        octopi.extend(row);
+    }
+
+    let cols = cols.context("Empty input")?;
+    if cols == 0 {
+        bail!("Empty rows in input");
     }
-    assert_eq!(octopii.len(), GRID_ROWS * GRID_COLS);
 
     Ok(Grid {
-        data: Matrix::new(octopii, GRID_COLS),
+        data: Matrix::new(octopi, cols),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use aoc2021::matrix::Matrix;
+
+    use super::Grid;
+
+    fn demo_grid() -> Grid {
+        let rows = [
+            "5483143223",
+            "2745854711",
+            "5264556173",
+            "6141336146",
+            "6357385478",
+            "4167524645",
+            "2176841721",
+            "6882881134",
+            "4846848554",
+            "5283751526",
+        ];
+
+        Grid {
+            data: Matrix::new(
+                rows.iter()
+                    .flat_map(|row| row.bytes().map(|byte| byte - b'0'))
+                    .collect(),
+                10,
+            ),
+        }
+    }
+
+    #[test]
+    fn demo() {
+        let grid = demo_grid();
+
+        let flashes: usize = grid
+            .history()
+            .take(100)
+            .map(|(_, flashed)| flashed.count_ones())
+            .sum();
+        assert_eq!(flashes, 1656);
+        assert_eq!(grid.sync_point(), 195);
+    }
+
+    #[test]
+    fn cycle_matches_simulation() {
+        let grid = Grid {
+            data: Matrix::new(vec![1, 9, 3, 8, 0, 5], 3),
+        };
+        let cycle = grid.find_cycle();
+
+        let mut state = grid;
+        let mut flashes = 0;
+        for step in 0..500 {
+            assert_eq!(cycle.state_at(step), &state);
+            assert_eq!(cycle.total_flashes(step), flashes);
+
+            let (next, flashed) = state.step();
+            state = next;
+            flashes += flashed.count_ones() as u64;
+        }
+    }
+}
@@ -1,125 +1,474 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use petgraph::{
+    dot::{Config, Dot},
+    graph::{EdgeReference, NodeIndex, UnGraph},
+    visit::EdgeRef,
+};
 
-use aoc2021::util::input_lines;
+use aoc2021::util::input_string;
 
 fn main() -> Result<()> {
-    let graph = parse_input()?;
+    let caves = parse_input()?;
 
-    let paths = count_paths(&graph);
+    let paths = caves.count_paths(&RevisitPolicy::once())?;
     dbg!(paths);
 
-    let paths2 = count_paths2(&graph);
+    let paths2 = caves.count_paths(&RevisitPolicy::one_small_cave(2))?;
     dbg!(paths2);
 
+    let listed_paths = caves.paths(&RevisitPolicy::once())?;
+    let shortest_path = listed_paths
+        .iter()
+        .min_by_key(|path| path.len())
+        .context("No path from start to end")?;
+    dbg!(shortest_path);
+
+    println!("{}", caves.to_dot(Some(shortest_path)));
+
     Ok(())
 }
 
-fn count_paths(graph: &HashMap<String, Vec<String>>) -> u64 {
-    fn backtrack<'a>(
-        graph: &'a HashMap<String, Vec<String>>,
-        current: &str,
-        visited: &mut HashSet<&'a str>,
-    ) -> u64 {
-        if current == "end" {
-            return 1;
-        }
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Cave {
+    name: String,
+    is_small: bool,
+}
 
-        let mut count = 0;
+#[derive(Debug, Clone)]
+struct CaveSystem {
+    graph: UnGraph<Cave, ()>,
+    start: NodeIndex,
+    end: NodeIndex,
+}
 
-        for neighbour in graph.get(current).unwrap() {
-            if visited.contains(neighbour.as_str()) {
-                continue;
-            }
+/// Which caves a path may visit more than once. Big caves can always be revisited,
+/// and `start` can never be.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct RevisitPolicy {
+    /// A single small cave may be visited up to this many times.
+    repeat_limit: u32,
+    /// Small caves that may be visited any number of times.
+    unlimited: HashSet<String>,
+}
 
-            if neighbour.chars().all(|c| c.is_lowercase()) {
-                visited.insert(neighbour);
-            }
+impl RevisitPolicy {
+    fn once() -> Self {
+        Self::one_small_cave(1)
+    }
+
+    fn one_small_cave(repeat_limit: u32) -> Self {
+        Self {
+            repeat_limit,
+            unlimited: HashSet::new(),
+        }
+    }
+}
+
+/// The part of a partial path that determines how it may continue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PathState {
+    current: NodeIndex,
+    /// Bitmask of the limited small caves visited so far.
+    visited: u64,
+    /// The small cave that was visited more than once, and how many times.
+    repeated: Option<(NodeIndex, u32)>,
+}
 
-            count += backtrack(graph, neighbour, visited);
+impl CaveSystem {
+    /// Assigns a bit to every small cave whose visits are limited by the policy.
+    fn limited_caves(&self, policy: &RevisitPolicy) -> Result<HashMap<NodeIndex, u64>> {
+        let limited: Vec<NodeIndex> = self
+            .graph
+            .node_indices()
+            .filter(|&node| {
+                let cave = &self.graph[node];
+                node == self.start || (cave.is_small && !policy.unlimited.contains(&cave.name))
+            })
+            .collect();
 
-            visited.remove(neighbour.as_str());
+        if limited.len() > u64::BITS as usize {
+            bail!("Too many small caves: {}", limited.len());
         }
 
-        count
+        Ok(limited
+            .into_iter()
+            .enumerate()
+            .map(|(bit, node)| (node, 1 << bit))
+            .collect())
     }
 
-    let mut visited = HashSet::from(["start"]);
-    backtrack(graph, "start", &mut visited)
-}
+    fn initial_state(&self, limited: &HashMap<NodeIndex, u64>) -> PathState {
+        PathState {
+            current: self.start,
+            visited: limited[&self.start],
+            repeated: None,
+        }
+    }
+
+    /// The state after moving to `next`, if the policy allows it.
+    fn advance(
+        &self,
+        state: &PathState,
+        next: NodeIndex,
+        limited: &HashMap<NodeIndex, u64>,
+        policy: &RevisitPolicy,
+    ) -> Option<PathState> {
+        let mut new_state = PathState {
+            current: next,
+            ..*state
+        };
+
+        let bit = match limited.get(&next) {
+            Some(&bit) => bit,
+            None => return Some(new_state),
+        };
 
-fn count_paths2(graph: &HashMap<String, Vec<String>>) -> u64 {
-    fn backtrack<'a>(
-        graph: &'a HashMap<String, Vec<String>>,
-        current: &str,
-        visited: &mut HashSet<&'a str>,
-        small_cave_visited_twice: bool,
-    ) -> u64 {
-        if current == "end" {
-            return 1;
+        if state.visited & bit == 0 {
+            new_state.visited |= bit;
+            return Some(new_state);
         }
 
-        let mut count = 0;
+        if next == self.start {
+            return None;
+        }
 
-        for neighbour in graph.get(current).unwrap() {
-            let is_small_cave = neighbour.chars().all(|c| c.is_lowercase());
+        new_state.repeated = match state.repeated {
+            None if policy.repeat_limit >= 2 => Some((next, 2)),
+            Some((cave, times)) if cave == next && times < policy.repeat_limit => {
+                Some((cave, times + 1))
+            }
+            _ => return None,
+        };
 
-            let neighbour_visited = visited.contains(neighbour.as_str());
+        Some(new_state)
+    }
+
+    fn count_paths(&self, policy: &RevisitPolicy) -> Result<u64> {
+        let limited = self.limited_caves(policy)?;
+        let initial_state = self.initial_state(&limited);
+
+        Ok(self.path_counts(policy, &limited, initial_state)?[&initial_state])
+    }
+
+    /// Counts the ways to the end from every state that can be reached from `initial_state`.
+    fn path_counts(
+        &self,
+        policy: &RevisitPolicy,
+        limited: &HashMap<NodeIndex, u64>,
+        initial_state: PathState,
+    ) -> Result<HashMap<PathState, u64>> {
+        let mut counter = PathCounter {
+            caves: self,
+            policy,
+            limited,
+            memo: HashMap::new(),
+            in_progress: HashSet::new(),
+            loops: vec![],
+        };
+
+        counter.count(initial_state)?;
+
+        // A loop only matters if it can be followed by a way to the end. Loops that
+        // can't were counted as dead ends.
+        if let Some(state) = counter.loops.iter().find(|state| counter.memo[state] > 0) {
+            bail!(
+                "Infinitely many paths loop through '{}'",
+                self.graph[state.current].name
+            );
+        }
+
+        Ok(counter.memo)
+    }
+
+    /// Lists every path from `start` to `end` that the policy allows, as cave names.
+    fn paths(&self, policy: &RevisitPolicy) -> Result<Vec<Vec<&str>>> {
+        let limited = self.limited_caves(policy)?;
+        let initial_state = self.initial_state(&limited);
 
-            let mut inserted = false;
+        // Counting first rejects policies that allow infinitely many paths, and lets the
+        // search skip states that can't reach the end, such as loops between big caves
+        let counts = self.path_counts(policy, &limited, initial_state)?;
 
-            let mut small_cave_visited_twice = small_cave_visited_twice;
+        let mut paths = vec![];
+        let mut path = vec![self.start];
+        let mut stack = vec![(initial_state, self.graph.neighbors(self.start))];
 
-            if is_small_cave {
-                if neighbour_visited {
-                    if small_cave_visited_twice || neighbour == "start" {
-                        continue;
-                    }
-                    small_cave_visited_twice = true;
-                } else {
-                    visited.insert(neighbour);
-                    inserted = true;
+        while let Some((state, neighbors)) = stack.last_mut() {
+            let next = match neighbors.next() {
+                Some(next) => next,
+                None => {
+                    stack.pop();
+                    path.pop();
+                    continue;
                 }
+            };
+
+            let next_state = match self.advance(state, next, &limited, policy) {
+                Some(next_state) if next == self.end || counts[&next_state] > 0 => next_state,
+                _ => continue,
+            };
+
+            path.push(next);
+            if next == self.end {
+                paths.push(
+                    path.iter()
+                        .map(|&node| self.graph[node].name.as_str())
+                        .collect(),
+                );
+                path.pop();
+            } else {
+                stack.push((next_state, self.graph.neighbors(next)));
+            }
+        }
+
+        Ok(paths)
+    }
+
+    /// Renders the cave system in Graphviz DOT format. Small caves are drawn as
+    /// ellipses and big caves as boxes, and the caves and passages of `highlight`
+    /// are drawn in red.
+    fn to_dot(&self, highlight: Option<&[&str]>) -> String {
+        let highlight = highlight.unwrap_or_default();
+
+        let highlighted_nodes: HashSet<&str> = highlight.iter().copied().collect();
+        let highlighted_edges: HashSet<(&str, &str)> = highlight
+            .iter()
+            .tuple_windows()
+            .flat_map(|(&a, &b)| [(a, b), (b, a)])
+            .collect();
+
+        let node_attributes = |_, (_, cave): (NodeIndex, &Cave)| {
+            let shape = if cave.is_small { "ellipse" } else { "box" };
+            let mut attributes = format!("label = \"{}\" shape = {}", cave.name, shape);
+            if highlighted_nodes.contains(cave.name.as_str()) {
+                attributes += " color = red penwidth = 2";
+            }
+            attributes
+        };
+
+        let edge_attributes = |_, edge: EdgeReference<()>| {
+            let a = self.graph[edge.source()].name.as_str();
+            let b = self.graph[edge.target()].name.as_str();
+            if highlighted_edges.contains(&(a, b)) {
+                "color = red penwidth = 2".to_owned()
             } else {
-                assert!(!neighbour_visited);
+                String::new()
             }
+        };
+
+        let dot = Dot::with_attr_getters(
+            &self.graph,
+            &[Config::NodeNoLabel, Config::EdgeNoLabel],
+            &edge_attributes,
+            &node_attributes,
+        );
+
+        // Labels are set through the attributes, so the weights are never printed
+        format!("{:?}", dot)
+    }
+}
+
+struct PathCounter<'a> {
+    caves: &'a CaveSystem,
+    policy: &'a RevisitPolicy,
+    limited: &'a HashMap<NodeIndex, u64>,
+    memo: HashMap<PathState, u64>,
+    in_progress: HashSet<PathState>,
+    /// States that were reached again while they were still being counted.
+    loops: Vec<PathState>,
+}
+
+impl<'a> PathCounter<'a> {
+    fn count(&mut self, state: PathState) -> Result<u64> {
+        if state.current == self.caves.end {
+            return Ok(1);
+        }
+
+        if let Some(&count) = self.memo.get(&state) {
+            return Ok(count);
+        }
 
-            count += backtrack(graph, neighbour, visited, small_cave_visited_twice);
+        // Reaching the same state again means that the path went around in a loop.
+        // This is counted as a dead end for now and checked once all counts are known.
+        if !self.in_progress.insert(state) {
+            self.loops.push(state);
+            return Ok(0);
+        }
 
-            if inserted {
-                visited.remove(neighbour.as_str());
+        let mut count = 0u64;
+        for next in self.caves.graph.neighbors(state.current) {
+            if let Some(next_state) = self.caves.advance(&state, next, self.limited, self.policy) {
+                count = count
+                    .checked_add(self.count(next_state)?)
+                    .context("Too many paths")?;
             }
         }
 
-        count
+        self.in_progress.remove(&state);
+        self.memo.insert(state, count);
+
+        Ok(count)
     }
+}
 
-    let mut visited = HashSet::from(["start"]);
-    backtrack(graph, "start", &mut visited, false)
+fn parse_input() -> Result<CaveSystem> {
+    input_string()?.parse()
 }
 
-fn parse_input() -> Result<HashMap<String, Vec<String>>> {
-    let mut result: HashMap<String, Vec<String>> = HashMap::new();
+impl FromStr for CaveSystem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut graph = UnGraph::new_undirected();
+        let mut nodes = HashMap::new();
+
+        let mut intern = |graph: &mut UnGraph<Cave, ()>, name: &str| {
+            *nodes.entry(name.to_owned()).or_insert_with(|| {
+                graph.add_node(Cave {
+                    name: name.to_owned(),
+                    is_small: name.chars().all(|c| c.is_lowercase()),
+                })
+            })
+        };
 
-    let mut add_edge = |a: &str, b: String| {
-        if let Some(neighbours) = result.get_mut(a) {
-            neighbours.push(b);
-        } else {
-            result.insert(a.to_string(), vec![b]);
+        for line in s.lines() {
+            let (a, b) = line.split_once('-').context("Missing delimiter")?;
+            let a = intern(&mut graph, a);
+            let b = intern(&mut graph, b);
+
+            graph.add_edge(a, b, ());
         }
-    };
 
-    for line in input_lines()? {
-        let line = line?;
+        let find = |name| {
+            graph
+                .node_indices()
+                .find(|&node| graph[node].name == name)
+                .with_context(|| format!("No '{}' cave", name))
+        };
+        let start = find("start")?;
+        let end = find("end")?;
+
+        Ok(Self { graph, start, end })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
 
-        let vertices = line.split_once('-').context("Missing delimiter")?;
-        let a = vertices.0.to_string();
-        let b = vertices.1.to_string();
+    use super::{CaveSystem, RevisitPolicy};
 
-        add_edge(&a, b.clone());
-        add_edge(&b, a);
+    fn with_unlimited(policy: RevisitPolicy, names: &[&str]) -> RevisitPolicy {
+        RevisitPolicy {
+            unlimited: names
+                .iter()
+                .map(|&name| name.to_owned())
+                .collect::<HashSet<_>>(),
+            ..policy
+        }
+    }
+
+    const SMALL: &str = "start-A\nstart-b\nA-c\nA-b\nb-d\nA-end\nb-end";
+    const LARGER: &str = "fs-end\nhe-DX\nfs-he\nstart-DX\npj-DX\nend-zg\nzg-sl\nzg-pj\npj-he\n\
+        RW-he\nfs-DX\npj-RW\nzg-RW\nstart-pj\nhe-WI\nzg-he\npj-fs\nstart-RW";
+
+    #[test]
+    fn count_paths() {
+        let caves: CaveSystem = SMALL.parse().unwrap();
+        assert_eq!(caves.count_paths(&RevisitPolicy::once()).unwrap(), 10);
+        assert_eq!(
+            caves
+                .count_paths(&RevisitPolicy::one_small_cave(2))
+                .unwrap(),
+            36
+        );
+
+        let caves: CaveSystem = LARGER.parse().unwrap();
+        assert_eq!(caves.count_paths(&RevisitPolicy::once()).unwrap(), 226);
+        assert_eq!(
+            caves
+                .count_paths(&RevisitPolicy::one_small_cave(2))
+                .unwrap(),
+            3509
+        );
     }
 
-    Ok(result)
+    #[test]
+    fn revisit_policies() {
+        let caves: CaveSystem = "start-a\na-end".parse().unwrap();
+        let policy = RevisitPolicy::one_small_cave(5);
+        assert_eq!(caves.count_paths(&policy).unwrap(), 1);
+
+        // Only a can be revisited, and b has to be visited in between
+        let caves: CaveSystem = "start-a\na-b\na-end".parse().unwrap();
+        assert_eq!(caves.count_paths(&RevisitPolicy::once()).unwrap(), 1);
+        let policy = RevisitPolicy::one_small_cave(2);
+        assert_eq!(caves.count_paths(&policy).unwrap(), 2);
+        let policy = RevisitPolicy::one_small_cave(3);
+        assert_eq!(caves.count_paths(&policy).unwrap(), 2);
+
+        let policy = with_unlimited(RevisitPolicy::one_small_cave(3), &["b"]);
+        assert_eq!(caves.count_paths(&policy).unwrap(), 3);
+        let policy = with_unlimited(RevisitPolicy::once(), &["a", "b"]);
+        assert!(caves.count_paths(&policy).is_err());
+    }
+
+    #[test]
+    fn paths() {
+        let caves: CaveSystem = SMALL.parse().unwrap();
+
+        let paths = caves.paths(&RevisitPolicy::once()).unwrap();
+        assert_eq!(paths.len(), 10);
+        assert!(paths.contains(&vec!["start", "A", "b", "A", "c", "A", "end"]));
+        assert!(paths.contains(&vec!["start", "b", "end"]));
+        assert!(paths
+            .iter()
+            .all(|path| path[0] == "start" && path.last() == Some(&"end")));
+
+        let paths = caves.paths(&RevisitPolicy::one_small_cave(2)).unwrap();
+        assert_eq!(paths.len(), 36);
+        assert!(paths.contains(&vec!["start", "A", "b", "A", "b", "A", "c", "A", "end"]));
+        assert_eq!(paths.iter().collect::<HashSet<_>>().len(), paths.len());
+
+        let caves: CaveSystem = "start-A\nA-B\nA-end".parse().unwrap();
+        assert!(caves.paths(&RevisitPolicy::once()).is_err());
+
+        let caves: CaveSystem = "start-b\nb-A\nA-B\nb-end".parse().unwrap();
+        assert_eq!(
+            caves.paths(&RevisitPolicy::once()).unwrap(),
+            [["start", "b", "end"]]
+        );
+    }
+
+    #[test]
+    fn to_dot() {
+        let caves: CaveSystem = "start-A\nA-end\nstart-b\nb-end".parse().unwrap();
+
+        let dot = caves.to_dot(None);
+        assert!(dot.starts_with("graph {"));
+        assert!(dot.contains("label = \"A\" shape = box"));
+        assert!(dot.contains("label = \"b\" shape = ellipse"));
+        assert!(!dot.contains("red"));
+
+        let dot = caves.to_dot(Some(&["start", "b", "end"]));
+        assert_eq!(dot.matches("color = red").count(), 5);
+        assert!(dot.contains("label = \"b\" shape = ellipse color = red"));
+        assert!(!dot.contains("label = \"A\" shape = box color = red"));
+    }
+
+    #[test]
+    fn adjacent_big_caves() {
+        let caves: CaveSystem = "start-A\nA-B\nA-end".parse().unwrap();
+        assert!(caves.count_paths(&RevisitPolicy::once()).is_err());
+
+        // The loop between A and B can't lead to the end
+        let caves: CaveSystem = "start-b\nb-A\nA-B\nb-end".parse().unwrap();
+        assert_eq!(caves.count_paths(&RevisitPolicy::once()).unwrap(), 1);
+    }
 }
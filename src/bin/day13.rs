@@ -1,81 +1,259 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, fmt::Display};
 
 use anyhow::{bail, Context, Result};
+use lazy_static::lazy_static;
 
 use aoc2021::util::input_lines;
 
+/// Dots as (column, row). Folds can move dots to negative coordinates when the far
+/// half is larger than the near one, so the coordinates are signed.
+type Paper = HashSet<(i32, i32)>;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct Instructions {
-    paper: HashSet<(u32, u32)>,
+    paper: Paper,
     folds: Vec<Fold>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Fold {
-    Up(u32),
-    Left(u32),
+    Up(i32),
+    Left(i32),
+    /// Folds along the diagonal `row - column = offset`, moving the dots below it
+    /// to above it. An offset of 0 transposes the lower half.
+    Diagonal(i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    fold: Fold,
+    paper: Paper,
 }
 
 fn main() -> Result<()> {
     let instructions = parse_input()?;
 
-    let after_one_fold = fold(&instructions.paper, instructions.folds[0]);
-    let dots_after_one_fold = after_one_fold.len();
+    let frames = animate(&instructions.paper, &instructions.folds)?;
+
+    let dots_after_one_fold = frames.first().context("No folds")?.paper.len();
     dbg!(dots_after_one_fold);
 
-    let mut paper = instructions.paper;
-    for instruction in instructions.folds {
-        paper = fold(&paper, instruction);
-    }
-    print_paper(&paper);
+    let last_frame = frames.last().unwrap();
+    print!("{}", last_frame);
+
+    let code = read_letters(&last_frame.paper)?;
+    dbg!(code);
 
     Ok(())
 }
 
-fn fold(paper: &HashSet<(u32, u32)>, instruction: Fold) -> HashSet<(u32, u32)> {
+fn fold(paper: &Paper, instruction: Fold) -> Result<Paper> {
     let mut result = HashSet::new();
 
-    for dot in paper.iter() {
-        match instruction {
-            Fold::Up(row) => {
-                assert_ne!(dot.1, row);
+    for &(col, row) in paper {
+        // Distance of the dot from the fold line, positive on the side that is folded over
+        let (distance, line) = match instruction {
+            Fold::Up(line) => (row.checked_sub(line), line),
+            Fold::Left(line) => (col.checked_sub(line), line),
+            Fold::Diagonal(offset) => (
+                row.checked_sub(col).and_then(|d| d.checked_sub(offset)),
+                offset,
+            ),
+        };
+        let distance = distance.context("Coordinate overflow")?;
 
-                if dot.1 < row {
-                    result.insert(*dot);
-                } else {
-                    result.insert((dot.0, row.checked_sub(dot.1 - row).unwrap()));
-                }
-            }
-            Fold::Left(column) => {
-                assert_ne!(dot.0, column);
+        if distance == 0 {
+            bail!(
+                "Dot ({}, {}) lies on the fold line {:?}",
+                col,
+                row,
+                instruction
+            );
+        }
 
-                if dot.0 < column {
-                    result.insert(*dot);
-                } else {
-                    result.insert((column.checked_sub(dot.0 - column).unwrap(), dot.1));
-                }
-            }
+        if distance < 0 {
+            result.insert((col, row));
+            continue;
         }
+
+        let folded = match instruction {
+            Fold::Up(_) => line.checked_sub(distance).map(|row| (col, row)),
+            Fold::Left(_) => line.checked_sub(distance).map(|col| (col, row)),
+            Fold::Diagonal(_) => row.checked_sub(line).zip(col.checked_add(line)),
+        };
+        result.insert(folded.context("Coordinate overflow")?);
     }
 
-    result
+    Ok(result)
 }
 
-fn print_paper(paper: &HashSet<(u32, u32)>) {
-    let max_row = paper.iter().map(|dot| dot.1).max().unwrap_or(0);
+/// Applies the folds in order, keeping the paper after every fold.
+fn animate(paper: &Paper, folds: &[Fold]) -> Result<Vec<Frame>> {
+    let mut frames: Vec<Frame> = vec![];
+
+    for &instruction in folds {
+        let previous = frames.last().map_or(paper, |frame| &frame.paper);
+        let paper = fold(previous, instruction)?;
+        frames.push(Frame {
+            fold: instruction,
+            paper,
+        });
+    }
+
+    Ok(frames)
+}
+
+/// Draws the bounding box of the dots.
+fn render_paper(paper: &Paper) -> String {
+    let min_col = paper.iter().map(|dot| dot.0).min().unwrap_or(0);
+    let min_row = paper.iter().map(|dot| dot.1).min().unwrap_or(0);
     let max_col = paper.iter().map(|dot| dot.0).max().unwrap_or(0);
+    let max_row = paper.iter().map(|dot| dot.1).max().unwrap_or(0);
 
-    for row in 0..=max_row {
-        let mut string = String::with_capacity(max_col.checked_add(1).unwrap().try_into().unwrap());
-        for col in 0..=max_col {
+    let mut string = String::new();
+    for row in min_row..=max_row {
+        for col in min_col..=max_col {
             if paper.contains(&(col, row)) {
                 string.push('#');
             } else {
                 string.push('.');
             }
         }
-        println!("{}", string);
+        string.push('\n');
     }
+    string
+}
+
+impl Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{:?}", self.fold)?;
+        write!(f, "{}", render_paper(&self.paper))
+    }
+}
+
+const GLYPH_HEIGHT: i32 = 6;
+
+/// The letters of the font, one string per row. Most letters are 4 columns wide
+/// and followed by an empty column, but the Y takes up all 5 columns.
+const FONT: [(char, [&str; GLYPH_HEIGHT as usize]); 18] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// A column of a glyph, with one bit per row from the top.
+type GlyphColumn = u8;
+
+/// The columns of each letter, without its empty columns at either side.
+fn font_glyphs() -> Vec<(char, Vec<GlyphColumn>)> {
+    FONT.iter()
+        .map(|(letter, rows)| {
+            let width = rows[0].len();
+            let columns: Vec<GlyphColumn> = (0..width)
+                .map(|col| {
+                    rows.iter()
+                        .enumerate()
+                        .filter(|(_, row)| row.as_bytes()[col] == b'#')
+                        .map(|(row, _)| 1 << row)
+                        .sum()
+                })
+                .collect();
+
+            let first = columns.iter().position(|&column| column != 0).unwrap();
+            let last = columns.iter().rposition(|&column| column != 0).unwrap();
+            (*letter, columns[first..=last].to_vec())
+        })
+        .collect()
+}
+
+fn glyph_string(columns: &[GlyphColumn]) -> String {
+    let mut string = String::new();
+    for row in 0..GLYPH_HEIGHT {
+        for column in columns {
+            string.push(if column >> row & 1 == 1 { '#' } else { '.' });
+        }
+        string.push('\n');
+    }
+    string
+}
+
+/// Reads a run of columns without empty columns as a sequence of letters. Letters
+/// are usually separated by empty columns, but nothing separates a Y from the
+/// letter after it, so a run can hold more than one letter.
+fn read_run(glyphs: &[(char, Vec<GlyphColumn>)], run: &[GlyphColumn]) -> Option<String> {
+    if run.is_empty() {
+        return Some(String::new());
+    }
+
+    glyphs.iter().find_map(|(letter, columns)| {
+        let rest = run.strip_prefix(columns.as_slice())?;
+        let mut letters = read_run(glyphs, rest)?;
+        letters.insert(0, *letter);
+        Some(letters)
+    })
+}
+
+/// Reads the letters written by the dots, which have to be glyphs of the font
+/// in a single line.
+fn read_letters(paper: &Paper) -> Result<String> {
+    lazy_static! {
+        static ref GLYPHS: Vec<(char, Vec<GlyphColumn>)> = font_glyphs();
+    }
+
+    let min_col = paper.iter().map(|dot| dot.0).min().unwrap_or(0);
+    let min_row = paper.iter().map(|dot| dot.1).min().unwrap_or(0);
+    let max_col = paper.iter().map(|dot| dot.0).max().unwrap_or(0);
+    let max_row = paper.iter().map(|dot| dot.1).max().unwrap_or(0);
+
+    if max_row - min_row >= GLYPH_HEIGHT {
+        bail!(
+            "Dots span {} rows, more than one line of text",
+            max_row - min_row + 1
+        );
+    }
+
+    let columns: Vec<GlyphColumn> = (min_col..=max_col)
+        .map(|col| {
+            (0..GLYPH_HEIGHT)
+                .filter(|&row| paper.contains(&(col, min_row + row)))
+                .map(|row| 1 << row)
+                .sum()
+        })
+        .collect();
+
+    let mut letters = String::new();
+    let mut unknown = vec![];
+
+    let runs = columns
+        .split(|&column| column == 0)
+        .filter(|run| !run.is_empty());
+    for (index, run) in runs.enumerate() {
+        match read_run(&GLYPHS, run) {
+            Some(run_letters) => letters.push_str(&run_letters),
+            None => unknown.push(format!("Glyph {}:\n{}", index, glyph_string(run))),
+        }
+    }
+
+    if !unknown.is_empty() {
+        bail!("Unknown glyphs\n{}", unknown.join(""));
+    }
+
+    Ok(letters)
 }
 
 fn parse_input() -> Result<Instructions> {
@@ -110,19 +288,17 @@ fn parse_input() -> Result<Instructions> {
     for line in lines {
         let line = line?;
 
-        let instruction_parts = line.split_once('=').context("Invalid fold instruction")?;
-
-        let coordinate: u32 = instruction_parts.1.parse()?;
-
-        let axis = instruction_parts
-            .0
-            .chars()
-            .last()
+        let (axis, coordinate) = line
+            .strip_prefix("fold along ")
+            .and_then(|instruction| instruction.split_once('='))
             .context("Invalid fold instruction")?;
 
+        let coordinate: i32 = coordinate.parse()?;
+
         let fold = match axis {
-            'y' => Fold::Up(coordinate),
-            'x' => Fold::Left(coordinate),
+            "y" => Fold::Up(coordinate),
+            "x" => Fold::Left(coordinate),
+            "y-x" => Fold::Diagonal(coordinate),
             _ => bail!("Invalid fold axis '{}'", axis),
         };
 
@@ -131,3 +307,168 @@ fn parse_input() -> Result<Instructions> {
 
     Ok(Instructions { paper, folds })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{animate, fold, read_letters, render_paper, Fold, Paper};
+
+    fn paper(rows: &[&str]) -> Paper {
+        let mut paper = HashSet::new();
+        for (row, line) in rows.iter().enumerate() {
+            for (col, char) in line.chars().enumerate() {
+                if char == '#' {
+                    paper.insert((col as i32, row as i32));
+                }
+            }
+        }
+        paper
+    }
+
+    #[test]
+    fn read_letters_from_font() {
+        let text = paper(&[
+            "#..#.####.###..####.#..#..##..#..#.####",
+            "#..#.#....#..#....#.#.#..#..#.#..#....#",
+            "#..#.###..#..#...#..##...#..#.#..#...#.",
+            "#..#.#....###...#...#.#..####.#..#..#..",
+            "#..#.#....#.#..#....#.#..#..#.#..#.#...",
+            ".##..#....#..#.####.#..#.#..#..##..####",
+        ]);
+        assert_eq!(read_letters(&text).unwrap(), "UFRZKAUZ");
+
+        let shifted: HashSet<_> = text.iter().map(|&(col, row)| (col + 3, row + 7)).collect();
+        assert_eq!(read_letters(&shifted).unwrap(), "UFRZKAUZ");
+    }
+
+    #[test]
+    fn read_letters_starting_with_blank_columns() {
+        let text = paper(&[
+            ".###.#..#..###",
+            "..#..#..#...#.",
+            "..#..####...#.",
+            "..#..#..#...#.",
+            "..#..#..#...#.",
+            ".###.#..#..###",
+        ]);
+        assert_eq!(read_letters(&text).unwrap(), "IHI");
+
+        let shifted: HashSet<_> = text.iter().map(|&(col, row)| (col - 7, row)).collect();
+        assert_eq!(read_letters(&shifted).unwrap(), "IHI");
+
+        let text = paper(&[
+            ".###...##",
+            "..#.....#",
+            "..#.....#",
+            "..#.....#",
+            "..#..#..#",
+            ".###..##.",
+        ]);
+        assert_eq!(read_letters(&text).unwrap(), "IJ");
+    }
+
+    #[test]
+    fn read_wide_letters() {
+        // Nothing separates the Y from the P
+        let text = paper(&[
+            "#..#.#...####..####",
+            "#..#.#...##..#.#...",
+            "####..#.#.#..#.###.",
+            "#..#...#..###..#...",
+            "#..#...#..#....#...",
+            "#..#...#..#....####",
+        ]);
+        assert_eq!(read_letters(&text).unwrap(), "HYPE");
+
+        let text = paper(&[
+            "#...#.###.###.",
+            "#...#..#..#..#",
+            ".#.#...#..#..#",
+            "..#....#..###.",
+            "..#....#..#...",
+            "..#...###.#...",
+        ]);
+        assert_eq!(read_letters(&text).unwrap(), "YIP");
+    }
+
+    #[test]
+    fn unknown_glyphs() {
+        let square = paper(&["#####", "#...#", "#...#", "#...#", "#####"]);
+        assert!(read_letters(&square).is_err());
+
+        let text = paper(&[
+            ".##..####",
+            "#..#.#..#",
+            "#..#.#..#",
+            "####.#..#",
+            "#..#.#..#",
+            "#..#.####",
+        ]);
+        let error = read_letters(&text).unwrap_err().to_string();
+        assert!(error.contains("Glyph 1:\n####\n#..#\n#..#\n#..#\n#..#\n####\n"));
+        assert!(!error.contains("Glyph 0"));
+    }
+
+    #[test]
+    fn symmetric_folds() {
+        let dots = paper(&[
+            "...#..#..#.",
+            "....#......",
+            "...........",
+            "#..........",
+            "...#....#.#",
+            "...........",
+            "...........",
+            "...........",
+            "...........",
+            "...........",
+            ".#....#.##.",
+            "....#......",
+            "......#...#",
+            "#..........",
+            "#.#........",
+        ]);
+
+        let frames = animate(&dots, &[Fold::Up(7), Fold::Left(5)]).unwrap();
+        assert_eq!(frames[0].paper.len(), 17);
+        assert_eq!(frames[1].paper.len(), 16);
+        assert_eq!(
+            frames[1].to_string(),
+            "Left(5)\n#####\n#...#\n#...#\n#...#\n#####\n"
+        );
+    }
+
+    #[test]
+    fn asymmetric_folds() {
+        let dots = paper(&["#.....", "......", "......", ".....#"]);
+
+        let folded = fold(&dots, Fold::Left(1)).unwrap();
+        assert_eq!(folded, HashSet::from([(0, 0), (-3, 3)]));
+        assert_eq!(render_paper(&folded), "...#\n....\n....\n#...\n");
+
+        let folded = fold(&folded, Fold::Up(1)).unwrap();
+        assert_eq!(folded, HashSet::from([(0, 0), (-3, -1)]));
+    }
+
+    #[test]
+    fn dot_on_fold_line() {
+        let dots = paper(&["#.#", "...", ".#."]);
+
+        assert!(fold(&dots, Fold::Up(2)).is_err());
+        assert!(fold(&dots, Fold::Left(2)).is_err());
+        assert!(fold(&dots, Fold::Diagonal(0)).is_err());
+        assert!(animate(&dots, &[Fold::Up(1), Fold::Left(1)]).is_err());
+    }
+
+    #[test]
+    fn diagonal_folds() {
+        let dots = paper(&[".#..", "....", "#...", "...#"]);
+
+        let folded = fold(&dots, Fold::Diagonal(1)).unwrap();
+        assert_eq!(folded, HashSet::from([(1, 0), (1, 1), (3, 3)]));
+
+        let transposed = fold(&paper(&["...", "#..", "##."]), Fold::Diagonal(0)).unwrap();
+        assert_eq!(transposed, HashSet::from([(1, 0), (2, 0), (2, 1)]));
+    }
+}
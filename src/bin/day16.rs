@@ -1,8 +1,9 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use itertools::Itertools;
+use num::{BigUint, One, Zero};
 
 use aoc2021::{
-    bitmap::{BitMap, BitMapRef},
+    bitmap::{BitMap, BitMapRef, BitReader, BitWriter},
     util::input_lines,
 };
 
@@ -14,7 +15,7 @@ struct Packet {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 enum PacketData {
-    Literal(u64),
+    Literal(BigUint),
     Sum(Vec<Packet>),
     Product(Vec<Packet>),
     Minimum(Vec<Packet>),
@@ -25,162 +26,122 @@ enum PacketData {
 }
 
 impl Packet {
-    fn parse<'a>(data: &BitMapRef<'a>) -> (Self, usize) {
-        let version: u64 = data.slice(0..3).try_into().unwrap();
-        let version: u8 = version.try_into().unwrap();
-
-        let type_id: u64 = data.slice(3..6).try_into().unwrap();
-        let (data, len) = match type_id {
-            4 => {
-                let mut literal = 0u64;
-                let mut len = 0usize;
-                for index in 0.. {
-                    let bits = data.slice(6 + index * 5..6 + (index + 1) * 5);
-
-                    let current: u64 = bits.slice(1..5).try_into().unwrap();
-                    literal <<= 4;
-                    literal |= current;
-
-                    len += 5;
-
-                    if !bits.get(0) {
-                        break;
-                    }
-                }
-                (PacketData::Literal(literal), len)
-            }
-            0 => {
-                let (packets, len) = Self::parse_array(&data.slice(6..data.len()));
-                (PacketData::Sum(packets), len)
-            }
-            1 => {
-                let (packets, len) = Self::parse_array(&data.slice(6..data.len()));
-                (PacketData::Product(packets), len)
-            }
-            2 => {
-                let (packets, len) = Self::parse_array(&data.slice(6..data.len()));
-                (PacketData::Minimum(packets), len)
-            }
-            3 => {
-                let (packets, len) = Self::parse_array(&data.slice(6..data.len()));
-                (PacketData::Maximum(packets), len)
-            }
-            5 => {
-                let (mut packets, len) = Self::parse_array(&data.slice(6..data.len()));
-
-                assert_eq!(packets.len(), 2);
-                let b = packets.pop().unwrap();
-                let a = packets.pop().unwrap();
+    fn parse(reader: &mut BitReader) -> Result<Self> {
+        let version = reader.read_bits(3)?.try_into()?;
+
+        let type_id = reader.read_bits(3)?;
+        let data = match type_id {
+            4 => PacketData::Literal(Self::parse_literal(reader)?),
+            0 => PacketData::Sum(Self::parse_array(reader)?),
+            1 => PacketData::Product(Self::parse_array(reader)?),
+            2 => PacketData::Minimum(Self::parse_array(reader)?),
+            3 => PacketData::Maximum(Self::parse_array(reader)?),
+            5 => PacketData::GreaterThan(Self::parse_pair(reader)?),
+            6 => PacketData::LessThan(Self::parse_pair(reader)?),
+            7 => PacketData::EqualTo(Self::parse_pair(reader)?),
+            _ => bail!("Unexpected packet type {}", type_id),
+        };
 
-                (PacketData::GreaterThan((Box::new(a), Box::new(b))), len)
-            }
-            6 => {
-                let (mut packets, len) = Self::parse_array(&data.slice(6..data.len()));
+        Ok(Self { version, data })
+    }
 
-                assert_eq!(packets.len(), 2);
-                let b = packets.pop().unwrap();
-                let a = packets.pop().unwrap();
+    fn parse_literal(reader: &mut BitReader) -> Result<BigUint> {
+        let mut literal = BitWriter::new();
 
-                (PacketData::LessThan((Box::new(a), Box::new(b))), len)
-            }
-            7 => {
-                let (mut packets, len) = Self::parse_array(&data.slice(6..data.len()));
+        loop {
+            let has_more = reader.read_bool()?;
 
-                assert_eq!(packets.len(), 2);
-                let b = packets.pop().unwrap();
-                let a = packets.pop().unwrap();
+            literal.write_slice(reader.read_slice(4)?);
 
-                (PacketData::EqualTo((Box::new(a), Box::new(b))), len)
-            }
-            _ => {
-                panic!("Unexpected packet type");
+            if !has_more {
+                break;
             }
-        };
+        }
 
-        (Self { version, data }, len + 6)
+        let literal = literal.finish();
+        BigUint::try_from(BitMapRef::from(&literal))
     }
 
-    fn parse_array<'a>(data: &BitMapRef<'a>) -> (Vec<Packet>, usize) {
-        let length_type_id = data.get(0);
+    fn parse_array(reader: &mut BitReader) -> Result<Vec<Packet>> {
+        let length_type_id = reader.read_bool()?;
 
         if length_type_id {
-            let num_packets: u64 = data.slice(1..12).try_into().unwrap();
-            let num_packets: usize = num_packets.try_into().unwrap();
-
-            let mut packets = Vec::with_capacity(num_packets);
-            let mut len = 0;
-            for _ in 0..num_packets {
-                let (packet, packet_len) = Self::parse(&data.slice(12 + len..data.len()));
-                packets.push(packet);
-                len += packet_len;
-            }
+            let num_packets = reader.read_bits(11)?;
 
-            (packets, len + 12)
+            (0..num_packets).map(|_| Self::parse(reader)).collect()
         } else {
-            let len: u64 = data.slice(1..16).try_into().unwrap();
-            let len: usize = len.try_into().unwrap();
+            let len = reader.read_bits(15)?.try_into()?;
+            let mut reader = reader.sub_reader(len)?;
 
             let mut packets = Vec::new();
-            let mut read = 0;
-            while read < len {
-                let (packet, packet_len) = Self::parse(&data.slice(16 + read..16 + len));
-                packets.push(packet);
-                read += packet_len;
+            while !reader.is_empty() {
+                packets.push(Self::parse(&mut reader)?);
             }
 
-            (packets, len + 16)
+            Ok(packets)
         }
     }
 
-    fn eval(&self) -> u64 {
+    fn parse_pair(reader: &mut BitReader) -> Result<(Box<Packet>, Box<Packet>)> {
+        let (a, b) = Self::parse_array(reader)?
+            .into_iter()
+            .collect_tuple()
+            .context("Expected exactly two sub-packets")?;
+
+        Ok((Box::new(a), Box::new(b)))
+    }
+
+    fn eval(&self) -> BigUint {
         match &self.data {
-            PacketData::Literal(literal) => *literal,
+            PacketData::Literal(literal) => literal.clone(),
             PacketData::Sum(packets) => packets.iter().map(Self::eval).sum(),
             PacketData::Product(packets) => packets.iter().map(Self::eval).product(),
             PacketData::Minimum(packets) => packets.iter().map(Self::eval).min().unwrap(),
             PacketData::Maximum(packets) => packets.iter().map(Self::eval).max().unwrap(),
             PacketData::GreaterThan((a, b)) => {
                 if a.eval() > b.eval() {
-                    1
+                    BigUint::one()
                 } else {
-                    0
+                    BigUint::zero()
                 }
             }
             PacketData::LessThan((a, b)) => {
                 if a.eval() < b.eval() {
-                    1
+                    BigUint::one()
                 } else {
-                    0
+                    BigUint::zero()
                 }
             }
             PacketData::EqualTo((a, b)) => {
                 if a.eval() == b.eval() {
-                    1
+                    BigUint::one()
                 } else {
-                    0
+                    BigUint::zero()
                 }
             }
         }
     }
 }
 
-impl From<&[u8]> for Packet {
-    fn from(bytes: &[u8]) -> Self {
-        let bitmap = BitMap::new(Vec::from(bytes));
-        let bitmap = bitmap.slice(0..bitmap.len());
-        Self::from(&bitmap)
+impl TryFrom<&BitMap> for Packet {
+    type Error = anyhow::Error;
+
+    fn try_from(bitmap: &BitMap) -> Result<Self, Self::Error> {
+        Self::try_from(&BitMapRef::from(bitmap))
     }
 }
 
-impl<'a> From<&BitMapRef<'a>> for Packet {
-    fn from(data: &BitMapRef<'a>) -> Self {
-        Self::parse(data).0
+impl<'a> TryFrom<&BitMapRef<'a>> for Packet {
+    type Error = anyhow::Error;
+
+    fn try_from(data: &BitMapRef<'a>) -> Result<Self, Self::Error> {
+        Self::parse(&mut data.reader())
     }
 }
 
 fn main() -> Result<()> {
-    let bytes = parse_input()?;
-    let packet = Packet::from(&bytes[..]);
+    let bitmap = parse_input()?;
+    let packet = Packet::try_from(&bitmap)?;
 
     let version_sum = sum_all_version_numbers(&packet);
     dbg!(version_sum);
@@ -200,37 +161,89 @@ fn sum_all_version_numbers(packet: &Packet) -> u32 {
         PacketData::Product(packets) => packets.iter().map(sum_all_version_numbers).sum(),
         PacketData::Minimum(packets) => packets.iter().map(sum_all_version_numbers).sum(),
         PacketData::Maximum(packets) => packets.iter().map(sum_all_version_numbers).sum(),
-        PacketData::GreaterThan((a, b)) => {
-            sum_all_version_numbers(&*a) + sum_all_version_numbers(&*b)
-        }
-        PacketData::LessThan((a, b)) => sum_all_version_numbers(&*a) + sum_all_version_numbers(&*b),
-        PacketData::EqualTo((a, b)) => sum_all_version_numbers(&*a) + sum_all_version_numbers(&*b),
+        PacketData::GreaterThan((a, b)) => sum_all_version_numbers(a) + sum_all_version_numbers(b),
+        PacketData::LessThan((a, b)) => sum_all_version_numbers(a) + sum_all_version_numbers(b),
+        PacketData::EqualTo((a, b)) => sum_all_version_numbers(a) + sum_all_version_numbers(b),
     };
 
     sum
 }
 
-fn parse_input() -> Result<Vec<u8>> {
+fn parse_input() -> Result<BitMap> {
     let line = if let Ok(line) = input_lines()?.exactly_one() {
         line?
     } else {
         bail!("Invalid number of lines in input file");
     };
 
-    if !line.is_ascii() {
-        bail!("Invalid characters in input");
+    BitMap::from_hex(line.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use aoc2021::bitmap::BitMap;
+
+    use super::{sum_all_version_numbers, Packet, PacketData};
+
+    fn parse(hex: &str) -> anyhow::Result<Packet> {
+        Packet::try_from(&BitMap::from_hex(hex).unwrap())
+    }
+
+    #[test]
+    fn literal() {
+        let packet = parse("D2FE28").unwrap();
+        assert_eq!(packet.version, 6);
+        assert_eq!(packet.data, PacketData::Literal(BigUint::from(2021u32)));
     }
 
-    if !line.len() % 2 == 0 {
-        bail!("Invalid count of characters in input");
+    #[test]
+    fn wide_literal() {
+        let packet = parse("12308421084210842109C269").unwrap();
+        let value = (BigUint::from(1u32) << 68u32) + 12345u32;
+        assert_eq!(packet.data, PacketData::Literal(value.clone()));
+        assert_eq!(packet.eval(), value);
     }
 
-    let mut bytes = Vec::with_capacity(line.len() / 2);
+    #[test]
+    fn version_sums() {
+        for (hex, sum) in [
+            ("8A004A801A8002F478", 16),
+            ("620080001611562C8802118E34", 12),
+            ("C0015000016115A2E0802F182340", 23),
+            ("A0016C880162017C3686B18A3D4780", 31),
+        ] {
+            assert_eq!(
+                sum_all_version_numbers(&parse(hex).unwrap()),
+                sum,
+                "{}",
+                hex
+            );
+        }
+    }
 
-    for index in 0..line.len() / 2 {
-        let byte = u8::from_str_radix(&line[index * 2..index * 2 + 2], 16)?;
-        bytes.push(byte);
+    #[test]
+    fn values() {
+        for (hex, value) in [
+            ("C200B40A82", 3u32),
+            ("04005AC33890", 54),
+            ("880086C3E88112", 7),
+            ("CE00C43D881120", 9),
+            ("D8005AC2A8F0", 1),
+            ("F600BC2D8F", 0),
+            ("9C005AC2F8F0", 0),
+            ("9C0141080250320F1802104A08", 1),
+        ] {
+            assert_eq!(parse(hex).unwrap().eval(), BigUint::from(value), "{}", hex);
+        }
     }
 
-    Ok(bytes)
+    #[test]
+    fn truncated_packets_fail() {
+        assert!(parse("").is_err());
+        assert!(parse("D2FE").is_err());
+        assert!(parse("8A004A801A8002").is_err());
+        assert!(parse("9C0141080250320F1802104A").is_err());
+    }
 }
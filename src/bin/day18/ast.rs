@@ -217,8 +217,8 @@ fn split(graph: &mut SnailfishGraph) -> bool {
     if let Control::Break(index) = control {
         let value = graph[index].unwrap();
 
-        let left = graph.add_node(Some(value.div_floor(&2)));
-        let right = graph.add_node(Some(value.div_ceil(&2)));
+        let left = graph.add_node(Some(Integer::div_floor(&value, &2)));
+        let right = graph.add_node(Some(Integer::div_ceil(&value, &2)));
 
         graph[index] = None;
         graph.add_edge(index, right, ());
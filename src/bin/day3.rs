@@ -1,104 +1,207 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
+use num::BigUint;
 
-use aoc2021::util::input_lines;
+use aoc2021::{
+    bitmap::{BitMap, BitMapRef},
+    util::input_string,
+};
 
 fn main() -> Result<()> {
-    let mut numbers = parse_input()?;
+    let numbers = parse_input()?;
 
-    power_consumption(&mut numbers);
+    let (gamma_rate, epsilon_rate) = power_consumption(&numbers)?;
+    dbg!(&gamma_rate);
+    dbg!(&epsilon_rate);
 
-    life_support_rating(&mut numbers);
+    let power_consumption = gamma_rate * epsilon_rate;
+    dbg!(power_consumption);
+
+    let (oxygen_generator_rating, co2_scrubber_rating) = life_support_rating(&numbers)?;
+    dbg!(&oxygen_generator_rating);
+    dbg!(&co2_scrubber_rating);
+
+    let life_support_rating = oxygen_generator_rating * co2_scrubber_rating;
+    dbg!(life_support_rating);
 
     Ok(())
 }
 
-fn parse_input() -> Result<Vec<u16>> {
-    input_lines()?
-        .map(|maybe_line| {
-            let line = maybe_line?;
-            Ok(u16::from_str_radix(&line, 2)?)
-        })
-        .collect()
+fn parse_input() -> Result<Vec<BitMap>> {
+    parse_report(&input_string()?)
 }
 
-fn count_binary_digits(number: u16) -> u32 {
-    u16::BITS - number.leading_zeros()
+fn parse_report(report: &str) -> Result<Vec<BitMap>> {
+    let numbers: Result<Vec<BitMap>> = report
+        .lines()
+        .map(|line| BitMap::from_binary_str(line.trim()))
+        .collect();
+    let numbers = numbers?;
+
+    let width = numbers.first().context("Empty input")?.len();
+    if let Some(index) = numbers.iter().position(|number| number.len() != width) {
+        bail!(
+            "Line {} has {} digits, expected {}",
+            index + 1,
+            numbers[index].len(),
+            width
+        );
+    }
+
+    Ok(numbers)
+}
+
+fn to_biguint(number: &BitMap) -> BigUint {
+    BigUint::try_from(BitMapRef::from(number)).unwrap()
 }
 
-fn power_consumption(numbers: &mut [u16]) {
-    // Assume that the leading digit in the original input is not 0
-    let digits = count_binary_digits(*numbers.iter().max().unwrap());
+fn count_ones(numbers: &[&BitMap], digit: usize) -> usize {
+    numbers.iter().filter(|number| number.get(digit)).count()
+}
 
-    let mut gamma_rate = 0u16;
-    for digit in 1..digits + 1 {
-        let mask = 1 << (digits - digit);
+/// Returns the gamma and epsilon rates.
+fn power_consumption(numbers: &[BitMap]) -> Result<(BigUint, BigUint)> {
+    let numbers: Vec<&BitMap> = numbers.iter().collect();
+    let digits = numbers.first().context("Empty report")?.len();
 
-        let (_, &mut median, _) =
-            numbers.select_nth_unstable_by_key((numbers.len() - 1) / 2, |&number| number & mask);
+    let mut gamma_rate = BitMap::zeroed(digits);
+    for digit in 0..digits {
+        let ones = count_ones(&numbers, digit);
+        let zeroes = numbers.len() - ones;
 
-        gamma_rate <<= 1;
-        gamma_rate |= ((median & mask) != 0) as u16;
+        if ones == zeroes {
+            bail!("Digit {} has as many ones as zeroes", digit);
+        }
+
+        gamma_rate.set(digit, ones > zeroes);
     }
-    dbg!(gamma_rate);
 
-    let epsilon_rate = (!gamma_rate) & ((1 << digits) - 1);
-    dbg!(epsilon_rate);
+    let epsilon_rate = !&gamma_rate;
 
-    let power_consumption = gamma_rate as u32 * epsilon_rate as u32;
-    dbg!(power_consumption);
+    Ok((to_biguint(&gamma_rate), to_biguint(&epsilon_rate)))
+}
+
+/// Returns the oxygen generator and CO2 scrubber ratings.
+fn life_support_rating(numbers: &[BitMap]) -> Result<(BigUint, BigUint)> {
+    let oxygen_generator_rating = find_rating(numbers, true).context("Oxygen generator rating")?;
+    let co2_scrubber_rating = find_rating(numbers, false).context("CO2 scrubber rating")?;
+
+    Ok((
+        to_biguint(oxygen_generator_rating),
+        to_biguint(co2_scrubber_rating),
+    ))
 }
 
-fn life_support_rating(numbers: &mut [u16]) {
-    // Assume that the leading digit in the original input is not 0
-    let digits = count_binary_digits(*numbers.iter().max().unwrap());
-
-    let sort_by_digit = |numbers, digit| sort_by_digit(numbers, digit, digits);
-
-    let (zeroes, ones) = sort_by_digit(numbers, 1);
-    let (mut oxygen, mut co2) = if ones.len() >= zeroes.len() {
-        (ones, zeroes)
-    } else {
-        (zeroes, ones)
-    };
-
-    for digit in 2..digits + 1 {
-        if oxygen.len() > 1 {
-            let (zeroes, ones) = sort_by_digit(oxygen, digit);
-            if ones.len() >= zeroes.len() {
-                oxygen = ones;
-            } else {
-                oxygen = zeroes;
-            }
+/// Repeatedly keeps the numbers with the most common digit (or the least common
+/// one, if `most_common` is false), preferring 1 (respectively 0) on ties.
+fn find_rating(numbers: &[BitMap], most_common: bool) -> Result<&BitMap> {
+    let mut candidates: Vec<&BitMap> = numbers.iter().collect();
+    let digits = numbers.first().context("Empty report")?.len();
+
+    for digit in 0..digits {
+        if candidates.len() <= 1 {
+            break;
         }
 
-        if co2.len() > 1 {
-            let (zeroes, ones) = sort_by_digit(co2, digit);
-            if zeroes.len() <= ones.len() {
-                co2 = zeroes;
-            } else {
-                co2 = ones;
-            }
+        let ones = count_ones(&candidates, digit);
+        let zeroes = candidates.len() - ones;
+
+        let keep = if most_common {
+            ones >= zeroes
+        } else {
+            ones < zeroes
+        };
+
+        if (keep && ones == 0) || (!keep && zeroes == 0) {
+            bail!(
+                "No numbers have {} in digit {}, all {} remaining numbers would be dropped",
+                keep as u8,
+                digit,
+                candidates.len()
+            );
         }
+
+        candidates.retain(|number| number.get(digit) == keep);
     }
 
-    assert!(oxygen.len() == 1);
-    let oxygen_generator_rating = oxygen[0];
-    dbg!(oxygen_generator_rating);
+    match candidates[..] {
+        [rating] => Ok(rating),
+        _ => bail!("{} numbers remain after all digits", candidates.len()),
+    }
+}
 
-    assert!(co2.len() == 1);
-    let co2_scrubber_rating = co2[0];
-    dbg!(co2_scrubber_rating);
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
 
-    let life_support_rating = oxygen_generator_rating as u32 * co2_scrubber_rating as u32;
-    dbg!(life_support_rating);
-}
+    use super::{find_rating, life_support_rating, parse_report, power_consumption};
+
+    const EXAMPLE: &str = "00100\n11110\n10110\n10111\n10101\n01111\n\
+        00111\n11100\n10000\n11001\n00010\n01010\n";
+
+    #[test]
+    fn example_report() {
+        let numbers = parse_report(EXAMPLE).unwrap();
+
+        let (gamma_rate, epsilon_rate) = power_consumption(&numbers).unwrap();
+        assert_eq!(gamma_rate, BigUint::from(22u32));
+        assert_eq!(epsilon_rate, BigUint::from(9u32));
+        assert_eq!(gamma_rate * epsilon_rate, BigUint::from(198u32));
+
+        let (oxygen, co2) = life_support_rating(&numbers).unwrap();
+        assert_eq!(oxygen, BigUint::from(23u32));
+        assert_eq!(co2, BigUint::from(10u32));
+        assert_eq!(oxygen * co2, BigUint::from(230u32));
+    }
+
+    #[test]
+    fn leading_zeroes() {
+        // No number has a leading 1, which used to shrink the inferred width
+        let numbers = parse_report("0010\n0011\n0001").unwrap();
+        assert_eq!(numbers[0].len(), 4);
+
+        let (gamma_rate, epsilon_rate) = power_consumption(&numbers).unwrap();
+        assert_eq!(gamma_rate, BigUint::from(0b0011u32));
+        assert_eq!(epsilon_rate, BigUint::from(0b1100u32));
+
+        assert!(parse_report("0010\n011").is_err());
+    }
 
-fn sort_by_digit(numbers: &mut [u16], digit: u32, total_digits: u32) -> (&mut [u16], &mut [u16]) {
-    let mask = 1 << (total_digits - digit);
+    #[test]
+    fn wide_report() {
+        let numbers = parse_report(&format!("1{0}0\n1{0}1\n0{0}1", "0".repeat(68))).unwrap();
+        assert_eq!(numbers[0].len(), 70);
+
+        let (gamma_rate, epsilon_rate) = power_consumption(&numbers).unwrap();
+        assert_eq!(gamma_rate, (BigUint::from(1u32) << 69u32) + 1u32);
+        assert_eq!(
+            epsilon_rate,
+            (BigUint::from(1u32) << 70u32) - 1u32 - &gamma_rate
+        );
+
+        let (oxygen, co2) = life_support_rating(&numbers).unwrap();
+        assert_eq!(oxygen, (BigUint::from(1u32) << 69u32) + 1u32);
+        assert_eq!(co2, BigUint::from(1u32));
+    }
 
-    numbers.sort_unstable_by_key(|&number| number & mask);
+    #[test]
+    fn gamma_tie() {
+        let numbers = parse_report("10\n01\n11").unwrap();
+        assert!(power_consumption(&numbers).is_ok());
 
-    let partition = numbers.partition_point(|&number| (number & mask) == 0);
+        let numbers = parse_report("10\n01").unwrap();
+        assert!(power_consumption(&numbers).is_err());
+    }
 
-    numbers.split_at_mut(partition)
+    #[test]
+    fn rating_errors() {
+        // Every number has a 1 in the first digit, so the least common digit is
+        // a 0 that no number has
+        let numbers = parse_report("10\n11").unwrap();
+        assert!(find_rating(&numbers, true).is_ok());
+        assert!(find_rating(&numbers, false).is_err());
+
+        // Duplicates can never be told apart
+        let numbers = parse_report("01\n01").unwrap();
+        assert!(find_rating(&numbers, true).is_err());
+    }
 }
@@ -1,45 +1,165 @@
-use anyhow::Result;
+use std::ops::{Add, Mul};
+
+use anyhow::{bail, Result};
+use num::{BigUint, One, Zero};
 
 use aoc2021::util::input_lines;
 
 fn main() -> Result<()> {
     let fish = parse_input()?;
+    let model = PopulationModel::default();
 
-    let demo_population = calculate_population(&[3, 4, 3, 1, 2], 80);
+    let demo_population = model.population(&[3, 4, 3, 1, 2], 80)?;
     dbg!(demo_population);
 
-    let population = calculate_population(&fish, 80);
+    let population = model.population(&fish, 80)?;
     dbg!(population);
 
-    let demo_population_long = calculate_population(&[3, 4, 3, 1, 2], 256);
+    let demo_population_long = model.population(&[3, 4, 3, 1, 2], 256)?;
     dbg!(demo_population_long);
 
-    let population_long = calculate_population(&fish, 256);
+    let population_long = model.population(&fish, 256)?;
     dbg!(population_long);
 
+    let population_trillion_mod = model.population_mod(&fish, 1_000_000_000_000, 1_000_000_007)?;
+    dbg!(population_trillion_mod);
+
     Ok(())
 }
 
-fn calculate_population(fish: &[u8], days: u32) -> u64 {
-    let mut cache = vec![0; days.try_into().unwrap()];
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PopulationModel {
+    /// Timer value of a fish right after it spawns.
+    reset_timer: usize,
+    /// Timer value of a newly spawned fish.
+    newborn_timer: usize,
+}
+
+impl Default for PopulationModel {
+    fn default() -> Self {
+        Self {
+            reset_timer: 6,
+            newborn_timer: 8,
+        }
+    }
+}
+
+type SquareMatrix<T> = Vec<Vec<T>>;
+
+impl PopulationModel {
+    fn states(&self) -> usize {
+        self.reset_timer.max(self.newborn_timer) + 1
+    }
 
-    fn lookup(cache: &[u64], fish: u8, days: u32) -> u64 {
-        let fish: u32 = fish.into();
+    /// The population grows exponentially, so the exact count takes time linear
+    /// in the number of days just to write down. Use [`Self::population_mod`]
+    /// for astronomically long periods.
+    fn population(&self, fish: &[u8], days: u64) -> Result<BigUint> {
+        self.evolve(fish, days, |value: BigUint| value)
+    }
 
-        if days <= fish.into() {
-            return 1;
+    fn population_mod(&self, fish: &[u8], days: u64, modulus: u64) -> Result<u64> {
+        if modulus == 0 {
+            bail!("Modulus must be positive");
         }
 
-        let index: usize = (days - fish - 1).try_into().unwrap();
-        cache[index]
+        let modulus = u128::from(modulus);
+        let population = self.evolve(fish, days, |value: u128| value % modulus)?;
+
+        Ok(population.try_into().unwrap())
     }
 
-    for day in 1..=days {
-        let index: usize = (day - 1).try_into().unwrap();
-        cache[index] = lookup(&cache, 6, day - 1) + lookup(&cache, 8, day - 1);
+    fn evolve<T>(&self, fish: &[u8], days: u64, reduce: impl Fn(T) -> T + Copy) -> Result<T>
+    where
+        T: Clone + Zero + One + Add<Output = T> + Mul<Output = T>,
+    {
+        let states = self.states();
+
+        let mut counts = vec![T::zero(); states];
+        for &timer in fish {
+            let timer = usize::from(timer);
+            if timer >= states {
+                bail!("Fish timer {} exceeds the maximum of {}", timer, states - 1);
+            }
+
+            counts[timer] = reduce(counts[timer].clone() + T::one());
+        }
+
+        let transition = power(&self.transition_matrix(), days, reduce);
+
+        let mut population = T::zero();
+        for row in &transition {
+            for (factor, count) in row.iter().zip(&counts) {
+                population = reduce(population + reduce(factor.clone() * count.clone()));
+            }
+        }
+
+        Ok(population)
     }
 
-    fish.iter().map(|fish| lookup(&cache, *fish, days)).sum()
+    /// Entry `[new][old]` is the number of fish with timer `new` that a single fish
+    /// with timer `old` turns into after one day.
+    fn transition_matrix<T: Clone + Zero + One>(&self) -> SquareMatrix<T> {
+        let states = self.states();
+
+        let mut matrix = vec![vec![T::zero(); states]; states];
+        for timer in 1..states {
+            matrix[timer - 1][timer] = T::one();
+        }
+        matrix[self.reset_timer][0] = matrix[self.reset_timer][0].clone() + T::one();
+        matrix[self.newborn_timer][0] = matrix[self.newborn_timer][0].clone() + T::one();
+
+        matrix
+    }
+}
+
+fn power<T>(
+    matrix: &SquareMatrix<T>,
+    mut exponent: u64,
+    reduce: impl Fn(T) -> T + Copy,
+) -> SquareMatrix<T>
+where
+    T: Clone + Zero + One + Add<Output = T> + Mul<Output = T>,
+{
+    let size = matrix.len();
+
+    let mut result = vec![vec![T::zero(); size]; size];
+    for (index, row) in result.iter_mut().enumerate() {
+        row[index] = T::one();
+    }
+
+    let mut base = matrix.clone();
+    while exponent > 0 {
+        if exponent & 1 != 0 {
+            result = multiply(&result, &base, reduce);
+        }
+
+        exponent >>= 1;
+        if exponent > 0 {
+            base = multiply(&base, &base, reduce);
+        }
+    }
+
+    result
+}
+
+fn multiply<T>(a: &SquareMatrix<T>, b: &SquareMatrix<T>, reduce: impl Fn(T) -> T) -> SquareMatrix<T>
+where
+    T: Clone + Zero + Add<Output = T> + Mul<Output = T>,
+{
+    let size = a.len();
+
+    (0..size)
+        .map(|row| {
+            (0..size)
+                .map(|col| {
+                    (0..size).fold(T::zero(), |sum, index| {
+                        reduce(sum + reduce(a[row][index].clone() * b[index][col].clone()))
+                    })
+                })
+                .collect()
+        })
+        .collect()
 }
 
 fn parse_input() -> Result<Vec<u8>> {
@@ -55,3 +175,46 @@ fn parse_input() -> Result<Vec<u8>> {
 
     Ok(counters)
 }
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use super::PopulationModel;
+
+    #[test]
+    fn demo_population() {
+        let model = PopulationModel::default();
+        let fish = [3, 4, 3, 1, 2];
+
+        assert_eq!(model.population(&fish, 0).unwrap(), BigUint::from(5u8));
+        assert_eq!(model.population(&fish, 18).unwrap(), BigUint::from(26u8));
+        assert_eq!(model.population(&fish, 80).unwrap(), BigUint::from(5934u16));
+        assert_eq!(
+            model.population_mod(&fish, 256, 1_000_000_007).unwrap(),
+            26984457539 % 1_000_000_007
+        );
+        assert!(model.population(&[9], 1).is_err());
+    }
+
+    #[test]
+    fn custom_timers() {
+        let doubling = PopulationModel {
+            reset_timer: 0,
+            newborn_timer: 0,
+        };
+        assert_eq!(
+            doubling.population(&[0], 100).unwrap(),
+            BigUint::from(1u8) << 100
+        );
+
+        let fibonacci = PopulationModel {
+            reset_timer: 0,
+            newborn_timer: 1,
+        };
+        assert_eq!(
+            fibonacci.population(&[0], 10).unwrap(),
+            BigUint::from(144u8)
+        );
+    }
+}
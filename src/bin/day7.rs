@@ -1,93 +1,183 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 
 use aoc2021::util::input_lines;
 
 fn main() -> Result<()> {
-    let mut positions = parse_input()?;
+    let positions = parse_input()?;
 
-    let mut demo_positions = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+    let demo_positions = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
 
-    let optimal_position_demo = find_optimal_position(&mut demo_positions);
+    let optimal_position_demo = LinearFuel.optimal_position(&demo_positions).unwrap();
     dbg!(optimal_position_demo);
 
-    let fuel_demo = calculate_total_distance(&demo_positions, optimal_position_demo);
+    let fuel_demo = total_fuel(&LinearFuel, &demo_positions, optimal_position_demo);
     dbg!(fuel_demo);
 
-    let optimal_position = find_optimal_position(&mut positions);
+    let optimal_position = LinearFuel.optimal_position(&positions).unwrap();
     dbg!(optimal_position);
 
-    let fuel = calculate_total_distance(&positions, optimal_position);
+    let fuel = total_fuel(&LinearFuel, &positions, optimal_position);
     dbg!(fuel);
 
-    let optimal_position_demo2 = find_optimal_position2(&demo_positions);
+    let optimal_position_demo2 = TriangularFuel.optimal_position(&demo_positions).unwrap();
     dbg!(optimal_position_demo2);
 
-    let fuel_demo2 = calculate_total_fuel2(&demo_positions, optimal_position_demo2);
+    let fuel_demo2 = total_fuel(&TriangularFuel, &demo_positions, optimal_position_demo2);
     dbg!(fuel_demo2);
 
-    let optimal_position2 = find_optimal_position2(&mut positions);
+    let optimal_position2 = TriangularFuel.optimal_position(&positions).unwrap();
     dbg!(optimal_position2);
 
-    let fuel2 = calculate_total_fuel2(&positions, optimal_position2);
+    let fuel2 = total_fuel(&TriangularFuel, &positions, optimal_position2);
     dbg!(fuel2);
 
+    let squared = ConvexFuel(|distance: u64| u128::from(distance) * u128::from(distance));
+
+    let optimal_position_squared = squared.optimal_position(&positions).unwrap();
+    dbg!(optimal_position_squared);
+
+    let fuel_squared = total_fuel(&squared, &positions, optimal_position_squared);
+    dbg!(fuel_squared);
+
     Ok(())
 }
 
-fn find_optimal_position(positions: &mut [u32]) -> u32 {
-    let (_, &mut median, _) = positions.select_nth_unstable((positions.len() - 1) / 2);
-    median
-}
+/// The cost of moving a crab by some distance. Costs must be convex and
+/// non-decreasing in the distance, so that the total fuel is convex in the target.
+trait FuelModel {
+    fn cost(&self, distance: u64) -> u128;
 
-fn find_optimal_position2(positions: &[u32]) -> u32 {
-    let min = *positions.iter().min().unwrap();
-    let max = *positions.iter().max().unwrap();
+    /// Finds the position that minimizes the total fuel, or `None` if there are no crabs.
+    fn optimal_position(&self, positions: &[u64]) -> Option<u64> {
+        let mut low = *positions.iter().min()?;
+        let mut high = *positions.iter().max()?;
 
-    (min..=max)
-        .min_by_key(|candidate| calculate_total_fuel2(positions, *candidate))
-        .unwrap()
-}
+        let fuel = |target| total_fuel(self, positions, target);
 
-fn calculate_total_distance(positions: &[u32], target: u32) -> u32 {
-    positions
-        .iter()
-        .map(|&position| {
-            if position >= target {
-                position - target
+        while high - low > 2 {
+            let third = (high - low) / 3;
+            let left = low + third;
+            let right = high - third;
+
+            let left_fuel = fuel(left);
+            let right_fuel = fuel(right);
+
+            if left_fuel < right_fuel {
+                high = right - 1;
+            } else if left_fuel > right_fuel {
+                low = left + 1;
             } else {
-                target - position
+                low = left;
+                high = right;
             }
-        })
-        .sum()
+        }
+
+        (low..=high).min_by_key(|&target| fuel(target))
+    }
 }
 
-fn calculate_total_fuel2(positions: &[u32], target: u32) -> u32 {
-    positions
-        .iter()
-        .map(|&position| {
-            let distance = if position >= target {
-                position - target
-            } else {
-                target - position
-            };
+/// Every step costs one unit of fuel.
+struct LinearFuel;
+
+impl FuelModel for LinearFuel {
+    fn cost(&self, distance: u64) -> u128 {
+        distance.into()
+    }
 
-            let fuel = (0 + distance) * (distance + 1) / 2;
+    fn optimal_position(&self, positions: &[u64]) -> Option<u64> {
+        if positions.is_empty() {
+            return None;
+        }
+
+        let middle = (positions.len() - 1) / 2;
+        let mut positions = positions.to_vec();
+        let (_, &mut median, _) = positions.select_nth_unstable(middle);
+        Some(median)
+    }
+}
 
-            fuel
-        })
+/// Each step costs one unit more than the previous one.
+struct TriangularFuel;
+
+impl FuelModel for TriangularFuel {
+    fn cost(&self, distance: u64) -> u128 {
+        let distance = u128::from(distance);
+        distance * (distance + 1) / 2
+    }
+
+    fn optimal_position(&self, positions: &[u64]) -> Option<u64> {
+        let min = *positions.iter().min()?;
+        let max = *positions.iter().max()?;
+
+        // The optimum is within half a step of the mean
+        let sum: u128 = positions.iter().map(|&position| u128::from(position)).sum();
+        let mean: u64 = (sum / positions.len() as u128).try_into().unwrap();
+
+        (mean.saturating_sub(1)..=mean + 2)
+            .map(|target| target.clamp(min, max))
+            .min_by_key(|&target| total_fuel(self, positions, target))
+    }
+}
+
+/// An arbitrary convex cost function.
+struct ConvexFuel<F: Fn(u64) -> u128>(F);
+
+impl<F: Fn(u64) -> u128> FuelModel for ConvexFuel<F> {
+    fn cost(&self, distance: u64) -> u128 {
+        (self.0)(distance)
+    }
+}
+
+fn total_fuel<M: FuelModel + ?Sized>(model: &M, positions: &[u64], target: u64) -> u128 {
+    positions
+        .iter()
+        .map(|&position| model.cost(position.abs_diff(target)))
         .sum()
 }
 
-fn parse_input() -> Result<Vec<u32>> {
+fn parse_input() -> Result<Vec<u64>> {
     let lines: Result<Vec<_>> = input_lines()?.collect();
     let lines = lines?;
-    assert_eq!(lines.len(), 1);
+    let line = lines.first().context("Empty input")?;
 
-    let positions: Result<Vec<_>> = lines[0]
+    let positions: Result<Vec<_>> = line
         .split(',')
-        .map(|counter| Ok(counter.parse()?))
+        .map(|position| Ok(position.trim().parse()?))
         .collect();
-    let counters = positions?;
 
-    Ok(counters)
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{total_fuel, ConvexFuel, FuelModel, LinearFuel, TriangularFuel};
+
+    const DEMO_POSITIONS: [u64; 10] = [16, 1, 2, 0, 4, 2, 7, 1, 2, 14];
+
+    #[test]
+    fn closed_forms_match_ternary_search() {
+        let linear = ConvexFuel(u128::from);
+        let triangular = ConvexFuel(|distance| TriangularFuel.cost(distance));
+
+        for positions in [
+            &DEMO_POSITIONS[..],
+            &[5],
+            &[1, 1000],
+            &[3, 3, 9, 1_000_000_000],
+        ] {
+            for (closed_form, search) in [
+                (&LinearFuel as &dyn FuelModel, &linear as &dyn FuelModel),
+                (&TriangularFuel, &triangular),
+            ] {
+                let fuel = |model: &dyn FuelModel| {
+                    total_fuel(model, positions, model.optimal_position(positions).unwrap())
+                };
+                assert_eq!(fuel(closed_form), fuel(search));
+            }
+        }
+
+        assert_eq!(LinearFuel.optimal_position(&[]), None);
+        assert_eq!(TriangularFuel.optimal_position(&[]), None);
+        assert_eq!(linear.optimal_position(&[]), None);
+    }
 }
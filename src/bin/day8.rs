@@ -1,13 +1,11 @@
-use std::{collections::HashMap, str::FromStr};
+use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
 use aoc2021::util::input_lines;
-use itertools::Itertools;
 
-const SIGNAL_NAMES: &[char; 7] = &['a', 'b', 'c', 'd', 'e', 'f', 'g'];
-const SEGMENT_NAMES: &[char; 7] = SIGNAL_NAMES;
-const DIGITS: [(&str, u8); 10] = [
+const STANDARD_SEGMENTS: &str = "abcdefg";
+const STANDARD_DIGITS: [(&str, u8); 10] = [
     ("abcefg", 0),
     ("cf", 1),
     ("acdeg", 2),
@@ -21,28 +19,27 @@
 ];
 
 struct DisplayData {
-    unique_patterns: [String; 10],
-    displayed_patterns: [String; 4],
+    unique_patterns: Vec<String>,
+    displayed_patterns: Vec<String>,
 }
 
 impl FromStr for DisplayData {
     type Err = anyhow::Error;
 
     fn from_str(string: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<_> = string.split('|').collect();
-        let parts: [_; 2] = parts.try_into().unwrap();
+        let (unique_patterns, displayed_patterns) = string
+            .split_once('|')
+            .with_context(|| format!("No separator in line '{}'", string))?;
 
-        let unique_patterns: Vec<_> = parts[0]
+        let unique_patterns = unique_patterns
             .split_whitespace()
             .map(|pattern| pattern.to_string())
             .collect();
-        let unique_patterns: [_; 10] = unique_patterns.try_into().unwrap();
 
-        let displayed_patterns: Vec<_> = parts[1]
+        let displayed_patterns = displayed_patterns
             .split_whitespace()
             .map(|pattern| pattern.to_string())
             .collect();
-        let displayed_patterns: [_; 4] = displayed_patterns.try_into().unwrap();
 
         Ok(Self {
             unique_patterns,
@@ -51,179 +48,285 @@ fn from_str(string: &str) -> Result<Self, Self::Err> {
     }
 }
 
-fn main() -> Result<()> {
-    let notes = parse_input()?;
+/// A seven-segment style display: the names of its segments, and which segments
+/// light up for every digit. Segment sets are bitmasks over the segment names.
+struct SegmentDisplay {
+    segments: Vec<char>,
+    digits: Vec<(u64, u8)>,
+}
 
-    let digits_with_unique_amount_of_segments = count_digits_with_unique_amount_of_segments(&notes);
-    dbg!(digits_with_unique_amount_of_segments);
+impl SegmentDisplay {
+    fn new(segments: &str, digits: &[(&str, u8)]) -> Result<Self> {
+        let segments: Vec<char> = segments.chars().collect();
+        if segments.len() > u64::BITS.try_into().unwrap() {
+            bail!("Too many segments: {}", segments.len());
+        }
+        if (1..segments.len()).any(|index| segments[..index].contains(&segments[index])) {
+            bail!(
+                "Duplicate segment names in '{}'",
+                segments.iter().collect::<String>()
+            );
+        }
 
-    let display_sum: u32 = notes.iter().map(|data| decode_display(data)).sum();
-    dbg!(display_sum);
+        let mut display = Self {
+            segments,
+            digits: vec![],
+        };
 
-    Ok(())
-}
+        for &(pattern, digit) in digits {
+            let mask = display.pattern_mask(pattern)?;
+            if display.digits.iter().any(|&(other, _)| other == mask) {
+                bail!("Digit {} has the same segments as another digit", digit);
+            }
+            display.digits.push((mask, digit));
+        }
 
-fn count_digits_with_unique_amount_of_segments(notes: &[DisplayData]) -> u32 {
-    notes
-        .iter()
-        .map(|data| {
-            data.displayed_patterns
+        Ok(display)
+    }
+
+    fn standard() -> Self {
+        Self::new(STANDARD_SEGMENTS, &STANDARD_DIGITS).unwrap()
+    }
+
+    fn pattern_mask(&self, pattern: &str) -> Result<u64> {
+        let mut mask = 0;
+
+        for char in pattern.chars() {
+            let index = self
+                .segments
                 .iter()
-                .filter(|pattern| match pattern.len() {
-                    2 | 4 | 3 | 7 => true,
-                    _ => false,
-                })
-                .count()
-        })
-        .sum::<usize>()
-        .try_into()
-        .unwrap()
-}
+                .position(|&segment| segment == char)
+                .with_context(|| format!("Unknown segment '{}' in pattern '{}'", char, pattern))?;
+            mask |= 1 << index;
+        }
 
-fn decode_display(data: &DisplayData) -> u32 {
-    let mappings = solve_segment_mapping(&data.unique_patterns);
+        Ok(mask)
+    }
+
+    fn all_segments(&self) -> u64 {
+        u64::MAX
+            .checked_shr(u64::BITS - self.segments.len() as u32)
+            .unwrap_or(0)
+    }
 
-    for mapping in mappings {
-        let mapped_patterns: Vec<String> = data
-            .displayed_patterns
+    fn has_unique_length(&self, pattern: &str) -> bool {
+        let len = pattern.chars().count();
+        self.digits
             .iter()
-            .map(|pattern| {
-                pattern
-                    .chars()
-                    .map(|char| mapping.get(&char).unwrap())
-                    .collect()
-            })
-            .collect();
+            .filter(|(mask, _)| mask.count_ones() as usize == len)
+            .count()
+            == 1
+    }
 
-        let digits: Option<Vec<u8>> = mapped_patterns
+    fn digit(&self, mask: u64) -> Option<u8> {
+        self.digits
             .iter()
-            .map(|pattern| segment_pattern_to_digit(pattern))
+            .find(|&&(digit_mask, _)| digit_mask == mask)
+            .map(|&(_, digit)| digit)
+    }
+
+    /// Finds the unique wiring under which every pattern shows a digit.
+    /// The result maps each wire (by index into the segment names) to the segment it drives.
+    fn solve(&self, patterns: &[String]) -> Result<Vec<usize>> {
+        let patterns: Result<Vec<u64>> = patterns
+            .iter()
+            .map(|pattern| self.pattern_mask(pattern))
             .collect();
+        let patterns = patterns?;
+
+        let mut domains = vec![self.all_segments(); self.segments.len()];
 
-        if let Some(digits) = digits {
-            let number = digits
-                .into_iter()
-                .fold(0u32, |acc, digit| acc * 10 + digit as u32);
-            return number;
+        // A wire in a pattern drives a segment of some digit of the same length,
+        // and a wire outside of it drives a segment outside of such a digit.
+        for &pattern in &patterns {
+            let (lit, unlit) = self
+                .digits
+                .iter()
+                .filter(|(mask, _)| mask.count_ones() == pattern.count_ones())
+                .fold((0, 0), |(lit, unlit), (mask, _)| {
+                    (lit | mask, unlit | (self.all_segments() & !mask))
+                });
+
+            for (wire, domain) in domains.iter_mut().enumerate() {
+                *domain &= if pattern & (1 << wire) != 0 {
+                    lit
+                } else {
+                    unlit
+                };
+            }
+        }
+
+        let mut solver = Solver {
+            display: self,
+            patterns: &patterns,
+            domains: &domains,
+            assignment: vec![None; self.segments.len()],
+            used: 0,
+            solutions: vec![],
+        };
+        solver.backtrack();
+
+        match &solver.solutions[..] {
+            [] => bail!("No wiring is consistent with the patterns"),
+            [solution] => Ok(solution.clone()),
+            _ => bail!("The patterns are consistent with more than one wiring"),
         }
     }
 
-    panic!("No solution found!");
+    fn decode(&self, data: &DisplayData) -> Result<u64> {
+        let wiring = self.solve(&data.unique_patterns)?;
+
+        data.displayed_patterns
+            .iter()
+            .try_fold(0, |number, pattern| {
+                let wires = self.pattern_mask(pattern)?;
+                let segments = (0..wiring.len())
+                    .filter(|wire| wires & (1 << wire) != 0)
+                    .fold(0, |segments, wire| segments | 1 << wiring[wire]);
+
+                let digit = self
+                    .digit(segments)
+                    .with_context(|| format!("Pattern '{}' is not a digit", pattern))?;
+
+                Ok(number * 10 + u64::from(digit))
+            })
+    }
 }
 
-fn solve_segment_mapping(unique_patterns: &[String; 10]) -> Vec<HashMap<char, char>> {
-    let mut cache = HashMap::new();
-    let mut solutions = vec![];
-    backtrack(&mut cache, unique_patterns, &mut solutions);
-    assert!(!solutions.is_empty());
-
-    solutions
-        .into_iter()
-        .map(|solution| {
-            let result: HashMap<char, char> = solution
-                .into_iter()
-                .map(|(signal, segment)| (segment, signal))
-                .collect();
-            assert_eq!(result.len(), SEGMENT_NAMES.len());
-
-            result
-        })
-        .collect()
+struct Solver<'a> {
+    display: &'a SegmentDisplay,
+    patterns: &'a [u64],
+    domains: &'a [u64],
+    assignment: Vec<Option<usize>>,
+    used: u64,
+    solutions: Vec<Vec<usize>>,
 }
 
-fn backtrack(
-    assignment: &mut HashMap<char, char>,
-    unique_patterns: &[String; 10],
-    solutions: &mut Vec<HashMap<char, char>>,
-) {
-    if assignment.len() == SIGNAL_NAMES.len() {
-        solutions.push(assignment.clone());
-        return;
-    }
+impl<'a> Solver<'a> {
+    fn backtrack(&mut self) {
+        // Two solutions are enough to know that the wiring is ambiguous
+        if self.solutions.len() > 1 {
+            return;
+        }
 
-    let variable = *SIGNAL_NAMES
-        .iter()
-        .find(|signal| !assignment.contains_key(&signal))
-        .unwrap();
+        let wire = (0..self.assignment.len())
+            .filter(|&wire| self.assignment[wire].is_none())
+            .min_by_key(|&wire| (self.domains[wire] & !self.used).count_ones());
 
-    let values = SEGMENT_NAMES
-        .iter()
-        .filter(|segment| !assignment.values().contains(segment))
-        .copied()
-        .collect_vec();
-
-    for value in values {
-        assignment.insert(variable, value);
-        if is_valid_assignment(assignment, unique_patterns) {
-            backtrack(assignment, unique_patterns, solutions);
+        let wire = match wire {
+            Some(wire) => wire,
+            None => {
+                self.solutions
+                    .push(self.assignment.iter().flatten().copied().collect());
+                return;
+            }
+        };
+
+        let candidates = self.domains[wire] & !self.used;
+        for segment in (0..self.assignment.len()).filter(|segment| candidates & (1 << segment) != 0)
+        {
+            self.assignment[wire] = Some(segment);
+            self.used |= 1 << segment;
+
+            if self.is_consistent() {
+                self.backtrack();
+            }
+
+            self.assignment[wire] = None;
+            self.used &= !(1 << segment);
         }
-        assignment.remove(&variable);
     }
-}
 
-fn is_valid_assignment(assignment: &HashMap<char, char>, unique_patterns: &[String; 10]) -> bool {
-    let one = unique_patterns
-        .iter()
-        .find(|pattern| pattern.len() == 2)
-        .unwrap();
-    let seven = unique_patterns
-        .iter()
-        .find(|pattern| pattern.len() == 3)
-        .unwrap();
-    let four = unique_patterns
-        .iter()
-        .find(|pattern| pattern.len() == 4)
-        .unwrap();
-
-    // Unary constraints
-    for (signal, segment) in assignment {
-        for pattern in [one, seven, four] {
-            match pattern.len() {
-                // 1
-                2 => {
-                    let signals = ['c', 'f'];
-                    if signals.contains(signal) && !pattern.contains(*segment) {
-                        return false;
-                    }
-                }
-
-                // 7
-                3 => {
-                    let signals = ['a', 'c', 'f'];
-                    if signals.contains(signal) && !pattern.contains(*segment) {
-                        return false;
-                    }
-                }
+    fn is_consistent(&self) -> bool {
+        self.patterns.iter().all(|&pattern| {
+            let mut segments = 0;
 
-                // 4
-                4 => {
-                    let signals = ['b', 'c', 'd', 'f'];
-                    if signals.contains(signal) && !pattern.contains(*segment) {
-                        return false;
-                    }
+            for (wire, segment) in self.assignment.iter().enumerate() {
+                if pattern & (1 << wire) == 0 {
+                    continue;
                 }
 
-                _ => {
-                    unreachable!();
+                match segment {
+                    Some(segment) => segments |= 1 << segment,
+                    None => return true,
                 }
             }
-        }
-    }
 
-    true
+            self.display.digit(segments).is_some()
+        })
+    }
 }
 
-fn segment_pattern_to_digit(pattern: &str) -> Option<u8> {
-    let mut chars: Vec<_> = pattern.chars().collect();
-    chars.sort();
-    let canonical_pattern: String = chars.into_iter().collect();
+fn main() -> Result<()> {
+    let notes = parse_input()?;
+    let display = SegmentDisplay::standard();
+
+    let digits_with_unique_amount_of_segments =
+        count_digits_with_unique_amount_of_segments(&display, &notes);
+    dbg!(digits_with_unique_amount_of_segments);
+
+    let display_sum = notes.iter().try_fold(0, |sum, data| {
+        Ok::<_, anyhow::Error>(sum + display.decode(data)?)
+    })?;
+    dbg!(display_sum);
 
-    DIGITS
+    Ok(())
+}
+
+fn count_digits_with_unique_amount_of_segments(
+    display: &SegmentDisplay,
+    notes: &[DisplayData],
+) -> usize {
+    notes
         .iter()
-        .find(|(pattern, _digit)| *pattern == canonical_pattern)
-        .map(|(_, digit)| *digit)
+        .flat_map(|data| &data.displayed_patterns)
+        .filter(|pattern| display.has_unique_length(pattern))
+        .count()
 }
 
 fn parse_input() -> Result<Vec<DisplayData>> {
-    input_lines()?.map(|line| Ok(line?.parse()?)).collect()
+    input_lines()?.map(|line| line?.parse()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplayData, SegmentDisplay};
+
+    #[test]
+    fn standard_display() {
+        let data: DisplayData =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf"
+                .parse()
+                .unwrap();
+
+        assert_eq!(SegmentDisplay::standard().decode(&data).unwrap(), 5353);
+    }
+
+    #[test]
+    fn custom_display() {
+        let display = SegmentDisplay::new("xyz", &[("x", 0), ("xy", 1), ("xyz", 2)]).unwrap();
+        let data: DisplayData = "z yz xyz | xyz z zy".parse().unwrap();
+
+        assert_eq!(display.decode(&data).unwrap(), 201);
+        assert!(SegmentDisplay::new("xx", &[]).is_err());
+    }
+
+    #[test]
+    fn unsolvable_patterns() {
+        let display = SegmentDisplay::new("xyz", &[("x", 0), ("xy", 1), ("xyz", 2)]).unwrap();
+
+        let ambiguous: DisplayData = "xyz | xyz".parse().unwrap();
+        assert!(display
+            .decode(&ambiguous)
+            .unwrap_err()
+            .to_string()
+            .contains("more than one"));
+
+        let impossible: DisplayData = "x y | x".parse().unwrap();
+        assert!(display
+            .decode(&impossible)
+            .unwrap_err()
+            .to_string()
+            .contains("No wiring"));
+    }
 }
@@ -1,6 +1,7 @@
 use std::{
     cmp::Reverse,
-    collections::{HashMap, HashSet},
+    collections::{BinaryHeap, HashSet},
+    fmt::{Display, Formatter},
 };
 
 use anyhow::{Context, Result};
@@ -17,19 +18,6 @@ struct HeightMap {
 }
 
 impl HeightMap {
-    fn gradient_direction(&self, pos: &Position) -> Position {
-        let neighbour = self
-            .data
-            .neighbours(pos)
-            .min_by_key(|neighbour| self.data.get(neighbour))
-            .unwrap_or(*pos);
-        if self.data.get(&neighbour) <= self.data.get(pos) {
-            neighbour
-        } else {
-            *pos
-        }
-    }
-
     fn low_points(&self) -> impl Iterator<Item = Position> + '_ {
         self.data
             .all_points()
@@ -49,39 +37,136 @@ fn risk_level(&self, pos: &Position) -> u16 {
         height + 1
     }
 
-    fn basins(&self) -> HashMap<Position, HashSet<Position>> {
-        let mut result = HashMap::new();
+    /// Regional minima: connected areas of equal height that have no lower
+    /// neighbours. Each of these is where a basin drains to.
+    fn regional_minima(&self, is_wall: &impl Fn(u8) -> bool) -> Vec<Vec<Position>> {
+        let mut visited = HashSet::new();
+        let mut minima = vec![];
+
+        for point in self.data.all_points() {
+            let height = *self.data.get(&point);
+            if is_wall(height) || visited.contains(&point) {
+                continue;
+            }
+
+            let mut plateau = vec![point];
+            let mut is_minimum = true;
+            visited.insert(point);
+
+            let mut index = 0;
+            while index < plateau.len() {
+                let current = plateau[index];
+                index += 1;
+
+                for neighbour in self.data.neighbours(&current) {
+                    let neighbour_height = *self.data.get(&neighbour);
+                    if is_wall(neighbour_height) {
+                        continue;
+                    }
+
+                    if neighbour_height < height {
+                        is_minimum = false;
+                    } else if neighbour_height == height && visited.insert(neighbour) {
+                        plateau.push(neighbour);
+                    }
+                }
+            }
 
-        for low_point in self.low_points() {
-            result.insert(low_point, HashSet::new());
+            if is_minimum {
+                minima.push(plateau);
+            }
         }
 
-        let mut cache = HashMap::new();
+        minima
+    }
 
-        for point in self.data.all_points() {
-            if *self.data.get(&point) == 9 {
-                continue;
+    /// Segments the map into basins by flooding it from the regional minima upwards.
+    /// A cell belongs to the basin whose water reaches it first, and among basins
+    /// that reach it at the same level, to the one that got there in fewer steps.
+    fn watershed(&self, is_wall: impl Fn(u8) -> bool) -> Segmentation {
+        let minima = self.regional_minima(&is_wall);
+
+        let mut labels = Matrix::new(
+            vec![None; self.data.rows() * self.data.cols()],
+            self.data.cols(),
+        );
+        let mut queue = BinaryHeap::new();
+        let mut order = 0usize;
+
+        for (label, plateau) in minima.iter().enumerate() {
+            for point in plateau {
+                *labels.get_mut(point) = Some(label);
+                queue.push(Reverse((*self.data.get(point), order, *point)));
+                order += 1;
             }
+        }
+
+        let mut sizes = vec![0; minima.len()];
+
+        while let Some(Reverse((_, _, point))) = queue.pop() {
+            let label = labels.get(&point).unwrap();
+            sizes[label] += 1;
 
-            let mut path = vec![point];
-            let mut current = point;
-            while !result.contains_key(&current) {
-                if let Some(low_point) = cache.get(&current) {
-                    current = *low_point;
-                } else {
-                    current = self.gradient_direction(&current);
+            for neighbour in self.data.neighbours(&point) {
+                let height = *self.data.get(&neighbour);
+                if is_wall(height) || labels.get(&neighbour).is_some() {
+                    continue;
                 }
-                path.push(current);
+
+                *labels.get_mut(&neighbour) = Some(label);
+                queue.push(Reverse((height, order, neighbour)));
+                order += 1;
             }
+        }
+
+        let basins = minima
+            .into_iter()
+            .zip(sizes)
+            .map(|(low_points, size)| Basin { low_points, size })
+            .collect();
 
-            result.get_mut(&current).unwrap().extend(&path);
+        Segmentation { labels, basins }
+    }
+}
 
-            for step in path {
-                cache.insert(step, current);
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Basin {
+    low_points: Vec<Position>,
+    size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Segmentation {
+    /// The basin of every cell, as an index into `basins`, or `None` for walls.
+    labels: Matrix<Option<usize>>,
+    basins: Vec<Basin>,
+}
+
+impl Display for Segmentation {
+    /// Draws every basin with its own letter, uppercase at its low points, and walls as `#`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in 0..self.labels.rows() {
+            for col in 0..self.labels.cols() {
+                let pos = Position { row, col };
+
+                let char = match *self.labels.get(&pos) {
+                    Some(label) => {
+                        let char = char::from(b'a' + (label % 26) as u8);
+                        if self.basins[label].low_points.contains(&pos) {
+                            char.to_ascii_uppercase()
+                        } else {
+                            char
+                        }
+                    }
+                    None => '#',
+                };
+
+                write!(f, "{}", char)?;
             }
+            writeln!(f)?;
         }
 
-        result
+        Ok(())
     }
 }
 
@@ -94,10 +179,11 @@ fn main() -> Result<()> {
         .sum();
     dbg!(risk_sum);
 
-    let basins = map.basins();
-    let basin_sizes: Vec<_> = basins
-        .into_values()
-        .map(|basin| basin.len())
+    let segmentation = map.watershed(|height| height == 9);
+    let basin_sizes: Vec<_> = segmentation
+        .basins
+        .into_iter()
+        .map(|basin| basin.size)
         .sorted_unstable_by_key(|len| Reverse(*len))
         .collect();
     let size_product: usize = basin_sizes[0..3].iter().product();
@@ -132,3 +218,64 @@ fn parse_line(line: &str) -> impl Iterator<Item = u8> + '_ {
             .unwrap() as u8
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use aoc2021::matrix::{Matrix, Position};
+
+    use super::HeightMap;
+
+    fn height_map(rows: &[&str]) -> HeightMap {
+        let data: Vec<u8> = rows
+            .iter()
+            .flat_map(|row| row.bytes().map(|byte| byte - b'0'))
+            .collect();
+
+        HeightMap {
+            data: Matrix::new(data, rows[0].len()),
+        }
+    }
+
+    #[test]
+    fn demo_basins() {
+        let map = height_map(&[
+            "2199943210",
+            "3987894921",
+            "9856789892",
+            "8767896789",
+            "9899965678",
+        ]);
+
+        let segmentation = map.watershed(|height| height == 9);
+        let mut sizes: Vec<_> = segmentation.basins.iter().map(|basin| basin.size).collect();
+        sizes.sort_unstable();
+        assert_eq!(sizes, [3, 9, 9, 14]);
+    }
+
+    #[test]
+    fn plateaus() {
+        let map = height_map(&["11155", "55511", "33333"]);
+
+        let segmentation = map.watershed(|height| height == 9);
+        assert_eq!(segmentation.basins.len(), 2);
+        assert_eq!(
+            segmentation.basins[0].low_points,
+            [
+                Position { row: 0, col: 0 },
+                Position { row: 0, col: 1 },
+                Position { row: 0, col: 2 },
+            ]
+        );
+        assert_eq!(segmentation.to_string(), "AAAab\naaaBB\nbbbbb\n");
+    }
+
+    #[test]
+    fn custom_walls() {
+        let map = height_map(&["101", "222"]);
+
+        let segmentation = map.watershed(|height| height >= 1);
+        assert_eq!(segmentation.basins.len(), 1);
+        assert_eq!(segmentation.basins[0].size, 1);
+        assert_eq!(segmentation.to_string(), "#A#\n###\n");
+    }
+}
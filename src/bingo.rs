@@ -1,75 +1,903 @@
-const BINGO_ROWS: usize = 5;
-const BINGO_COLS: usize = 5;
-const BINGO_CELLS: usize = BINGO_ROWS * BINGO_COLS;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::{Display, Formatter},
+    str::FromStr,
+    sync::Arc,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Board {
-    numbers: [u32; BINGO_CELLS],
-    marked: [bool; BINGO_CELLS],
+use anyhow::{bail, Context, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+/// A board that keeps count of how many cells of each winning set are marked,
+/// so that marking a cell and checking for a win don't need to scan the board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Board<const R: usize = 5, const C: usize = 5> {
+    numbers: [[u32; C]; R],
+    marked: [[bool; C]; R],
+    win_masks: Arc<WinMasks<R, C>>,
+    mask_marks: Vec<usize>,
+    complete_masks: usize,
+    unmarked_sum: u32,
 }
 
-impl Board {
-    pub fn new(numbers: &[u32; BINGO_CELLS]) -> Self {
+impl<const R: usize, const C: usize> Board<R, C> {
+    /// Creates a board that wins under the standard rules.
+    pub fn new(numbers: &[[u32; C]; R]) -> Self {
+        Self::with_win_masks(numbers, Arc::new(WinMasks::new(&WinRule::standard())))
+    }
+
+    pub fn with_win_masks(numbers: &[[u32; C]; R], win_masks: Arc<WinMasks<R, C>>) -> Self {
         Self {
             numbers: *numbers,
-            marked: Default::default(),
+            marked: [[false; C]; R],
+            mask_marks: vec![0; win_masks.masks.len()],
+            win_masks,
+            complete_masks: 0,
+            unmarked_sum: numbers.iter().flatten().sum(),
         }
     }
 
-    pub fn mark_number(&self, number: u32) -> Self {
-        let mut new_board = *self;
+    pub fn from_rows(rows: &[Vec<u32>]) -> Result<Self> {
+        if rows.len() != R {
+            bail!("Expected {} rows, got {}", R, rows.len());
+        }
+
+        let mut numbers = [[0; C]; R];
+        for (row, (numbers_row, input_row)) in numbers.iter_mut().zip(rows).enumerate() {
+            if input_row.len() != C {
+                bail!(
+                    "Expected {} columns in row {}, got {}",
+                    C,
+                    row,
+                    input_row.len()
+                );
+            }
+
+            numbers_row.copy_from_slice(input_row);
+        }
+
+        Ok(Self::new(&numbers))
+    }
+
+    pub fn rows(&self) -> usize {
+        R
+    }
+
+    pub fn cols(&self) -> usize {
+        C
+    }
 
-        for index in 0..new_board.numbers.len() {
-            if new_board.numbers[index] == number {
-                new_board.marked[index] = true;
+    pub fn number(&self, row: usize, col: usize) -> u32 {
+        assert!(row < R);
+        assert!(col < C);
+
+        self.numbers[row][col]
+    }
+
+    pub fn win_masks(&self) -> &Arc<WinMasks<R, C>> {
+        &self.win_masks
+    }
+
+    /// Changes the rules that the board wins by, keeping its marks.
+    pub fn set_win_masks(&mut self, win_masks: Arc<WinMasks<R, C>>) {
+        self.mask_marks = vec![0; win_masks.masks.len()];
+        self.complete_masks = 0;
+        self.win_masks = win_masks;
+
+        for row in 0..R {
+            for col in 0..C {
+                if self.marked[row][col] {
+                    self.count_mark(row, col);
+                }
             }
         }
+    }
 
+    /// Clears all marks.
+    pub fn reset(&mut self) {
+        self.marked = [[false; C]; R];
+        self.mask_marks.fill(0);
+        self.complete_masks = 0;
+        self.unmarked_sum = self.numbers.iter().flatten().sum();
+    }
+
+    pub fn mark_number(&self, number: u32) -> Self {
+        let mut new_board = self.clone();
+        new_board.mark_number_in_place(number);
         new_board
     }
 
+    pub fn mark_number_in_place(&mut self, number: u32) {
+        for row in 0..R {
+            for col in 0..C {
+                if self.numbers[row][col] == number {
+                    self.mark_cell(row, col);
+                }
+            }
+        }
+    }
+
+    /// Marks a single cell, returning whether it was previously unmarked.
+    pub fn mark_cell(&mut self, row: usize, col: usize) -> bool {
+        assert!(row < R);
+        assert!(col < C);
+
+        if self.marked[row][col] {
+            return false;
+        }
+
+        self.marked[row][col] = true;
+        self.unmarked_sum -= self.numbers[row][col];
+        self.count_mark(row, col);
+
+        true
+    }
+
+    fn count_mark(&mut self, row: usize, col: usize) {
+        for &mask_index in &self.win_masks.cell_masks[row * C + col] {
+            self.mask_marks[mask_index] += 1;
+            if self.mask_marks[mask_index] == self.win_masks.mask_sizes[mask_index] {
+                self.complete_masks += 1;
+            }
+        }
+    }
+
     pub fn is_marked(&self, row: usize, col: usize) -> bool {
-        assert!(row < BINGO_ROWS);
-        assert!(col < BINGO_COLS);
+        assert!(row < R);
+        assert!(col < C);
 
-        self.marked[row * BINGO_ROWS + col]
+        self.marked[row][col]
     }
 
+    /// Whether the board has won under its own rules.
     pub fn is_winning(&self) -> bool {
-        // Check rows
-        for row in 0..BINGO_ROWS {
-            if self.marked[row * BINGO_ROWS..row * BINGO_ROWS + BINGO_COLS] == [true; BINGO_COLS] {
-                return true;
+        self.complete_masks > 0
+    }
+
+    pub fn is_winning_by(&self, rules: &[WinRule<R, C>]) -> bool {
+        rules
+            .iter()
+            .flat_map(WinRule::masks)
+            .any(|mask| self.is_mask_marked(&mask))
+    }
+
+    pub fn is_mask_marked(&self, mask: &[[bool; C]; R]) -> bool {
+        (0..R).all(|row| (0..C).all(|col| !mask[row][col] || self.marked[row][col]))
+    }
+
+    pub fn sum_unmarked(&self) -> u32 {
+        self.unmarked_sum
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WinRule<const R: usize = 5, const C: usize = 5> {
+    AnyRow,
+    AnyColumn,
+    /// Either of the two diagonals. Only square boards have diagonals.
+    AnyDiagonal,
+    FourCorners,
+    Blackout,
+    /// A custom set of cells. An empty pattern never wins.
+    Pattern([[bool; C]; R]),
+}
+
+impl<const R: usize, const C: usize> WinRule<R, C> {
+    pub fn standard() -> Vec<Self> {
+        vec![Self::AnyRow, Self::AnyColumn]
+    }
+
+    /// All sets of cells that win the game under this rule when fully marked.
+    pub fn masks(&self) -> Vec<[[bool; C]; R]> {
+        let mask_of = |cells: &mut dyn Iterator<Item = (usize, usize)>| {
+            let mut mask = [[false; C]; R];
+            for (row, col) in cells {
+                mask[row][col] = true;
+            }
+            mask
+        };
+
+        match self {
+            Self::AnyRow => (0..R)
+                .map(|row| mask_of(&mut (0..C).map(|col| (row, col))))
+                .collect(),
+            Self::AnyColumn => (0..C)
+                .map(|col| mask_of(&mut (0..R).map(|row| (row, col))))
+                .collect(),
+            Self::AnyDiagonal => {
+                if R != C || R == 0 {
+                    return vec![];
+                }
+
+                vec![
+                    mask_of(&mut (0..R).map(|index| (index, index))),
+                    mask_of(&mut (0..R).map(|index| (index, C - 1 - index))),
+                ]
+            }
+            Self::FourCorners => {
+                if R == 0 || C == 0 {
+                    return vec![];
+                }
+
+                vec![mask_of(
+                    &mut [(0, 0), (0, C - 1), (R - 1, 0), (R - 1, C - 1)].into_iter(),
+                )]
+            }
+            Self::Blackout => vec![[[true; C]; R]],
+            Self::Pattern(mask) => {
+                if mask.iter().flatten().any(|&cell| cell) {
+                    vec![*mask]
+                } else {
+                    vec![]
+                }
             }
         }
+    }
+}
+
+/// The winning sets of cells of a list of rules, prepared for incremental counting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WinMasks<const R: usize = 5, const C: usize = 5> {
+    masks: Vec<[[bool; C]; R]>,
+    /// Indices of the masks that contain each cell, in row-major order.
+    cell_masks: Vec<Vec<usize>>,
+    mask_sizes: Vec<usize>,
+}
+
+impl<const R: usize, const C: usize> WinMasks<R, C> {
+    pub fn new(rules: &[WinRule<R, C>]) -> Self {
+        let masks: Vec<_> = rules.iter().flat_map(WinRule::masks).collect();
 
-        // Check columns
-        for col in 0..BINGO_COLS {
-            if (0..BINGO_ROWS).all(|row| self.is_marked(row, col)) {
-                return true;
+        let mut cell_masks = vec![vec![]; R * C];
+        for (mask_index, mask) in masks.iter().enumerate() {
+            for row in 0..R {
+                for col in 0..C {
+                    if mask[row][col] {
+                        cell_masks[row * C + col].push(mask_index);
+                    }
+                }
             }
         }
 
-        false
+        let mask_sizes = masks
+            .iter()
+            .map(|mask| mask.iter().flatten().filter(|&&cell| cell).count())
+            .collect();
+
+        Self {
+            masks,
+            cell_masks,
+            mask_sizes,
+        }
     }
 
-    pub fn sum_unmarked(&self) -> u32 {
-        self.marked
-            .iter()
-            .enumerate()
-            .filter_map(|(index, &marked)| {
-                if marked {
+    pub fn masks(&self) -> &[[[bool; C]; R]] {
+        &self.masks
+    }
+}
+
+/// A game whose boards play by its rules. The win masks and the index from numbers
+/// to cells are built once, when the game is created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game<const R: usize = 5, const C: usize = 5> {
+    pub numbers: Vec<u32>,
+    boards: Vec<Board<R, C>>,
+    rules: Vec<WinRule<R, C>>,
+    index: HashMap<u32, Vec<BoardCell>>,
+}
+
+impl<const R: usize, const C: usize> Game<R, C> {
+    pub fn new(numbers: Vec<u32>, boards: Vec<Board<R, C>>) -> Self {
+        Self::with_rules(numbers, boards, WinRule::standard())
+    }
+
+    pub fn with_rules(
+        numbers: Vec<u32>,
+        boards: Vec<Board<R, C>>,
+        rules: Vec<WinRule<R, C>>,
+    ) -> Self {
+        let win_masks = Arc::new(WinMasks::new(&rules));
+
+        let mut boards = boards;
+        for board in &mut boards {
+            board.set_win_masks(Arc::clone(&win_masks));
+        }
+
+        let mut index: HashMap<u32, Vec<BoardCell>> = HashMap::new();
+        for (board_index, board) in boards.iter().enumerate() {
+            for row in 0..R {
+                for col in 0..C {
+                    index
+                        .entry(board.number(row, col))
+                        .or_default()
+                        .push(BoardCell {
+                            board_index,
+                            row,
+                            col,
+                        });
+                }
+            }
+        }
+
+        Self {
+            numbers,
+            boards,
+            rules,
+            index,
+        }
+    }
+
+    pub fn boards(&self) -> &[Board<R, C>] {
+        &self.boards
+    }
+
+    pub fn rules(&self) -> &[WinRule<R, C>] {
+        &self.rules
+    }
+
+    /// The cells of every board that hold each number, in board order.
+    pub fn number_index(&self) -> &HashMap<u32, Vec<BoardCell>> {
+        &self.index
+    }
+
+    /// The turn on which each board completes, if it ever does, when every board
+    /// plays to the end regardless of other winners.
+    pub fn win_turns(&self) -> Vec<Option<usize>> {
+        self.play().win_turns()
+    }
+
+    /// Estimates the odds of each board by replaying the game with `trials`
+    /// random permutations of the drawn numbers. Boards that tie for the first
+    /// win share the credit for it.
+    pub fn simulate(&self, trials: usize, seed: u64) -> Vec<BoardOdds> {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut wins = vec![0f64; self.boards.len()];
+        let mut completions = vec![0usize; self.boards.len()];
+        let mut total_turns = vec![0usize; self.boards.len()];
+
+        let mut numbers = self.numbers.clone();
+        let mut play = self.play();
+        for _ in 0..trials {
+            numbers.shuffle(&mut rng);
+            play.restart(&numbers);
+
+            let win_turns = play.win_turns();
+
+            if let Some(first_turn) = win_turns.iter().flatten().min() {
+                let winners: Vec<usize> = (0..win_turns.len())
+                    .filter(|&board_index| win_turns[board_index] == Some(*first_turn))
+                    .collect();
+
+                for &board_index in &winners {
+                    wins[board_index] += 1.0 / winners.len() as f64;
+                }
+            }
+
+            for (board_index, win_turn) in win_turns.into_iter().enumerate() {
+                if let Some(win_turn) = win_turn {
+                    completions[board_index] += 1;
+                    total_turns[board_index] += win_turn;
+                }
+            }
+        }
+
+        (0..self.boards.len())
+            .map(|board_index| BoardOdds {
+                win_probability: if trials == 0 {
+                    0.0
+                } else {
+                    wins[board_index] / trials as f64
+                },
+                expected_win_turn: if completions[board_index] == 0 {
                     None
                 } else {
-                    Some(self.numbers[index])
+                    Some(total_turns[board_index] as f64 / completions[board_index] as f64)
+                },
+            })
+            .collect()
+    }
+
+    pub fn play(&self) -> Play<'_, R, C> {
+        Play {
+            game: self,
+            numbers: self.numbers.clone(),
+            boards: self.boards.clone(),
+            won: vec![false; self.boards.len()],
+            turn: 0,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoardCell {
+    pub board_index: usize,
+    pub row: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoardOdds {
+    /// Probability of being the first board to win.
+    pub win_probability: f64,
+    /// Mean turn on which the board completes, if it ever does.
+    pub expected_win_turn: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Win {
+    pub board_index: usize,
+    /// Index of the winning draw in [`Game::numbers`].
+    pub turn: usize,
+    pub drawn_number: u32,
+    pub score: u32,
+}
+
+/// Iterator over the wins of a game, in order. Boards that win on the same
+/// draw are yielded in board order, and a board stops playing once it has won.
+#[derive(Debug, Clone)]
+pub struct Play<'a, const R: usize, const C: usize> {
+    game: &'a Game<R, C>,
+    numbers: Vec<u32>,
+    boards: Vec<Board<R, C>>,
+    won: Vec<bool>,
+    turn: usize,
+    pending: VecDeque<Win>,
+}
+
+impl<'a, const R: usize, const C: usize> Play<'a, R, C> {
+    pub fn first(mut self) -> Option<Win> {
+        self.next()
+    }
+
+    /// Starts over with a different order of drawn numbers, reusing the boards.
+    fn restart(&mut self, numbers: &[u32]) {
+        self.numbers.clear();
+        self.numbers.extend_from_slice(numbers);
+
+        for board in &mut self.boards {
+            board.reset();
+        }
+        self.won.fill(false);
+        self.turn = 0;
+        self.pending.clear();
+    }
+
+    /// Plays the remaining draws, returning the turn on which each board wins.
+    fn win_turns(&mut self) -> Vec<Option<usize>> {
+        let mut win_turns = vec![None; self.boards.len()];
+
+        for win in self.by_ref() {
+            win_turns[win.board_index] = Some(win.turn);
+        }
+
+        win_turns
+    }
+}
+
+impl<'a, const R: usize, const C: usize> Iterator for Play<'a, R, C> {
+    type Item = Win;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let &drawn_number = self.numbers.get(self.turn)?;
+
+            // The index lists cells in board order, so ties are discovered in board order too
+            let mut winners = vec![];
+            for cell in self.game.index.get(&drawn_number).into_iter().flatten() {
+                let board_index = cell.board_index;
+                if self.won[board_index] {
+                    continue;
+                }
+
+                let board = &mut self.boards[board_index];
+                if !board.mark_cell(cell.row, cell.col) {
+                    continue;
+                }
+
+                if board.is_winning() && winners.last() != Some(&board_index) {
+                    winners.push(board_index);
                 }
+            }
+
+            for board_index in winners {
+                self.won[board_index] = true;
+                self.pending.push_back(Win {
+                    board_index,
+                    turn: self.turn,
+                    drawn_number,
+                    score: self.boards[board_index].sum_unmarked() * drawn_number,
+                });
+            }
+
+            self.turn += 1;
+        }
+
+        self.pending.pop_front()
+    }
+}
+
+impl<const R: usize, const C: usize> FromStr for Game<R, C> {
+    type Err = anyhow::Error;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut sections = sections(string).into_iter();
+
+        let numbers_section = sections.next().context("Empty input")?;
+        if numbers_section.lines.len() != 1 {
+            bail!(
+                "Expected a blank line after the drawn numbers on line {}",
+                numbers_section.first_line
+            );
+        }
+
+        let numbers: Result<Vec<u32>> = numbers_section.lines[0]
+            .split(',')
+            .map(|number| Ok(number.trim().parse()?))
+            .collect();
+        let numbers = numbers.with_context(|| {
+            format!(
+                "Invalid drawn numbers on line {}",
+                numbers_section.first_line
+            )
+        })?;
+
+        let boards: Result<Vec<Board<R, C>>> = sections
+            .enumerate()
+            .map(|(board_index, section)| {
+                parse_board(&section).with_context(|| format!("Invalid board {}", board_index))
             })
-            .sum()
+            .collect();
+
+        Ok(Self::new(numbers, boards?))
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Game {
-    pub numbers: Vec<u32>,
-    pub boards: Vec<Board>,
+impl<const R: usize, const C: usize> Display for Game<R, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let numbers: Vec<String> = self.numbers.iter().map(u32::to_string).collect();
+        writeln!(f, "{}", numbers.join(","))?;
+
+        let width = self
+            .boards
+            .iter()
+            .flat_map(|board| board.numbers.iter().flatten())
+            .map(|number| number.to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max(2);
+
+        for board in &self.boards {
+            writeln!(f)?;
+
+            for row in &board.numbers {
+                let row: Vec<String> = row
+                    .iter()
+                    .map(|number| format!("{:>width$}", number, width = width))
+                    .collect();
+                writeln!(f, "{}", row.join(" "))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Determines the dimensions of the boards in a game in the puzzle format,
+/// based on the first board.
+pub fn board_size(string: &str) -> Result<(usize, usize)> {
+    let sections = sections(string);
+    let board = sections.get(1).context("No boards in input")?;
+
+    Ok((board.lines.len(), board.lines[0].split_whitespace().count()))
+}
+
+struct Section<'a> {
+    first_line: usize,
+    lines: Vec<&'a str>,
+}
+
+fn sections(string: &str) -> Vec<Section<'_>> {
+    let mut sections = vec![];
+    let mut current: Option<Section> = None;
+
+    for (index, line) in string.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            sections.extend(current.take());
+            continue;
+        }
+
+        current
+            .get_or_insert_with(|| Section {
+                first_line: index + 1,
+                lines: vec![],
+            })
+            .lines
+            .push(line);
+    }
+    sections.extend(current);
+
+    sections
+}
+
+fn parse_board<const R: usize, const C: usize>(section: &Section) -> Result<Board<R, C>> {
+    if section.lines.len() != R {
+        bail!(
+            "Expected {} rows starting at line {}, got {}",
+            R,
+            section.first_line,
+            section.lines.len()
+        );
+    }
+
+    let rows: Result<Vec<Vec<u32>>> = section
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(row, line)| {
+            let line_number = section.first_line + row;
+
+            let numbers: Result<Vec<u32>> = line
+                .split_whitespace()
+                .map(|number| Ok(number.parse()?))
+                .collect();
+            let numbers =
+                numbers.with_context(|| format!("Invalid number on line {}", line_number))?;
+
+            if numbers.len() != C {
+                bail!(
+                    "Expected {} numbers on line {}, got {}",
+                    C,
+                    line_number,
+                    numbers.len()
+                );
+            }
+
+            Ok(numbers)
+        })
+        .collect();
+
+    Board::from_rows(&rows?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{board_size, Board, BoardCell, Game, Win, WinMasks, WinRule};
+
+    fn board_with_marks(marks: &[u32]) -> Board<3, 3> {
+        let mut board = Board::new(&[[1, 2, 3], [4, 5, 6], [7, 8, 9]]);
+        for &number in marks {
+            board = board.mark_number(number);
+        }
+        board
+    }
+
+    #[test]
+    fn standard_rules() {
+        assert!(!board_with_marks(&[1, 2, 4, 5]).is_winning());
+        assert!(board_with_marks(&[4, 5, 6]).is_winning());
+        assert!(board_with_marks(&[2, 5, 8]).is_winning());
+        assert!(!board_with_marks(&[1, 5, 9]).is_winning());
+    }
+
+    #[test]
+    fn variant_rules() {
+        let diagonals = [WinRule::AnyDiagonal];
+        assert!(board_with_marks(&[1, 5, 9]).is_winning_by(&diagonals));
+        assert!(board_with_marks(&[3, 5, 7]).is_winning_by(&diagonals));
+        assert!(!board_with_marks(&[1, 2, 3]).is_winning_by(&diagonals));
+
+        let corners = [WinRule::FourCorners];
+        assert!(board_with_marks(&[1, 3, 7, 9]).is_winning_by(&corners));
+        assert!(!board_with_marks(&[1, 3, 7]).is_winning_by(&corners));
+
+        let blackout = [WinRule::Blackout];
+        assert!(!board_with_marks(&[1, 2, 3, 4, 5, 6, 7, 8]).is_winning_by(&blackout));
+        assert!(board_with_marks(&[1, 2, 3, 4, 5, 6, 7, 8, 9]).is_winning_by(&blackout));
+
+        let plus = [WinRule::Pattern([
+            [false, true, false],
+            [true, true, true],
+            [false, true, false],
+        ])];
+        assert!(board_with_marks(&[2, 4, 5, 6, 8]).is_winning_by(&plus));
+        assert!(!board_with_marks(&[4, 5, 6]).is_winning_by(&plus));
+    }
+
+    #[test]
+    fn boards_win_by_their_own_rules() {
+        let blackout = Arc::new(WinMasks::new(&[WinRule::Blackout]));
+
+        let mut board = Board::<3, 3>::with_win_masks(&[[1, 2, 3], [4, 5, 6], [7, 8, 9]], blackout);
+        for number in [1, 2, 3] {
+            board.mark_number_in_place(number);
+        }
+        assert!(!board.is_winning());
+
+        // Switching to the standard rules counts the existing marks
+        board.set_win_masks(Arc::new(WinMasks::new(&WinRule::standard())));
+        assert!(board.is_winning());
+
+        board.reset();
+        assert!(!board.is_winning());
+        assert_eq!(board.sum_unmarked(), 45);
+    }
+
+    #[test]
+    fn game_boards_play_by_game_rules() {
+        let game = Game::with_rules(
+            vec![1, 2, 3, 4],
+            vec![Board::new(&[[1, 2], [3, 4]])],
+            vec![WinRule::Blackout],
+        );
+
+        let mut board = game.boards()[0].clone();
+        board.mark_number_in_place(1);
+        board.mark_number_in_place(2);
+        assert!(!board.is_winning());
+
+        assert_eq!(game.win_turns(), [Some(3)]);
+        assert_eq!(
+            game.number_index()[&3],
+            [BoardCell {
+                board_index: 0,
+                row: 1,
+                col: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn non_square_boards_have_no_diagonals() {
+        let mut board = Board::<2, 3>::new(&[[1, 2, 3], [4, 5, 6]]);
+        for number in 1..=6 {
+            board = board.mark_number(number);
+        }
+
+        assert!(!board.is_winning_by(&[WinRule::AnyDiagonal]));
+        assert!(board.is_winning_by(&[WinRule::FourCorners]));
+    }
+
+    #[test]
+    fn play_reports_ties_in_board_order() {
+        let game = Game::new(
+            vec![5, 1, 9, 3, 7],
+            vec![
+                Board::new(&[[1, 2], [3, 4]]),
+                Board::new(&[[5, 3], [9, 7]]),
+                Board::new(&[[5, 9], [6, 8]]),
+                Board::new(&[[9, 5], [6, 8]]),
+            ],
+        );
+
+        let wins: Vec<Win> = game.play().collect();
+        assert_eq!(
+            wins,
+            [
+                Win {
+                    board_index: 1,
+                    turn: 2,
+                    drawn_number: 9,
+                    score: 10 * 9,
+                },
+                Win {
+                    board_index: 2,
+                    turn: 2,
+                    drawn_number: 9,
+                    score: 14 * 9,
+                },
+                Win {
+                    board_index: 3,
+                    turn: 2,
+                    drawn_number: 9,
+                    score: 14 * 9,
+                },
+                Win {
+                    board_index: 0,
+                    turn: 3,
+                    drawn_number: 3,
+                    score: 6 * 3,
+                },
+            ]
+        );
+
+        assert_eq!(game.play().first(), Some(wins[0]));
+        assert_eq!(game.play().last(), Some(wins[3]));
+    }
+
+    #[test]
+    fn play_marks_every_copy_of_a_number() {
+        let game = Game::new(vec![1, 2], vec![Board::new(&[[1, 2, 2], [3, 4, 5]])]);
+
+        assert_eq!(
+            game.play().collect::<Vec<_>>(),
+            [Win {
+                board_index: 0,
+                turn: 1,
+                drawn_number: 2,
+                score: 12 * 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let input = "7,4,9,5\n\n\n 1  2 \n30 40\n\n\n5 6   \n 7  8\n\n";
+        assert_eq!(board_size(input).unwrap(), (2, 2));
+
+        let game: Game<2, 2> = input.parse().unwrap();
+        assert_eq!(
+            game,
+            Game::new(
+                vec![7, 4, 9, 5],
+                vec![
+                    Board::new(&[[1, 2], [30, 40]]),
+                    Board::new(&[[5, 6], [7, 8]]),
+                ],
+            )
+        );
+
+        let output = game.to_string();
+        assert_eq!(output, "7,4,9,5\n\n 1  2\n30 40\n\n 5  6\n 7  8\n");
+        assert_eq!(output.parse::<Game<2, 2>>().unwrap(), game);
+    }
+
+    #[test]
+    fn parse_reports_malformed_board() {
+        let error = "1,2\n\n1 2\n3 4\n\n5 6\n7\n"
+            .parse::<Game<2, 2>>()
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid board 1");
+        assert_eq!(
+            error.root_cause().to_string(),
+            "Expected 2 numbers on line 7, got 1"
+        );
+
+        assert!("1,2\n\n1 2\n3 4\n5 6\n".parse::<Game<2, 2>>().is_err());
+        assert!("1,x\n\n1 2\n3 4\n".parse::<Game<2, 2>>().is_err());
+        assert!("".parse::<Game<2, 2>>().is_err());
+    }
+
+    #[test]
+    fn win_turns() {
+        let game = Game::new(
+            vec![5, 1, 9, 3, 7],
+            vec![
+                Board::new(&[[1, 2], [3, 4]]),
+                Board::new(&[[5, 3], [9, 7]]),
+                Board::new(&[[2, 4], [6, 8]]),
+            ],
+        );
+
+        assert_eq!(game.win_turns(), [Some(3), Some(2), None]);
+    }
+
+    #[test]
+    fn simulate_is_reproducible() {
+        let game = Game::new(
+            (1..=8).collect(),
+            vec![
+                Board::new(&[[1, 2], [3, 4]]),
+                Board::new(&[[1, 2], [3, 4]]),
+                Board::new(&[[9, 10], [11, 12]]),
+            ],
+        );
+
+        let odds = game.simulate(200, 42);
+        assert_eq!(odds, game.simulate(200, 42));
+
+        // Identical boards always tie, and the last board can never complete
+        assert_eq!(odds[0], odds[1]);
+        assert!((odds[0].win_probability - 0.5).abs() < 1e-9);
+        assert_eq!(odds[2].win_probability, 0.0);
+        assert_eq!(odds[2].expected_win_turn, None);
+
+        let expected_win_turn = odds[0].expected_win_turn.unwrap();
+        assert!((1.0..=5.0).contains(&expected_win_turn));
+    }
 }
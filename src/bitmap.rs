@@ -1,15 +1,80 @@
-use std::ops::Range;
+use std::{
+    fmt::{Debug, Display, Formatter},
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Range},
+};
 
-use anyhow::bail;
+use anyhow::{bail, Result};
+use num::{BigUint, Zero};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+use crate::matrix::Position;
+
+// Bits past `len` in the last byte are always kept zeroed, so that the derived
+// comparisons and hashing only see the meaningful bits.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
 pub struct BitMap {
     data: Vec<u8>,
+    len: usize,
 }
 
 impl BitMap {
     pub fn new(data: Vec<u8>) -> Self {
-        Self { data }
+        let len = data.len() * 8;
+        Self { data, len }
+    }
+
+    pub fn zeroed(len: usize) -> Self {
+        Self {
+            data: vec![0; len.div_ceil(8)],
+            len,
+        }
+    }
+
+    pub fn from_hex(string: &str) -> Result<Self> {
+        if !string.len().is_multiple_of(2) {
+            bail!("Odd number of hex digits: {}", string.len());
+        }
+
+        let data: Result<Vec<u8>> = string
+            .as_bytes()
+            .chunks(2)
+            .enumerate()
+            .map(|(index, pair)| {
+                let digits = |digit: &u8| (*digit as char).to_digit(16);
+                match (digits(&pair[0]), digits(&pair[1])) {
+                    (Some(high), Some(low)) => Ok((high << 4 | low) as u8),
+                    _ => bail!("Invalid hex digits at position {}", index * 2),
+                }
+            })
+            .collect();
+
+        Ok(Self::new(data?))
+    }
+
+    pub fn from_binary_str(string: &str) -> Result<Self> {
+        let mut bitmap = Self::default();
+
+        for (index, digit) in string.chars().enumerate() {
+            match digit {
+                '0' => bitmap.push(false),
+                '1' => bitmap.push(true),
+                _ => bail!("Invalid binary digit {:?} at position {}", digit, index),
+            }
+        }
+
+        Ok(bitmap)
+    }
+
+    /// Fails unless the bitmap holds whole bytes, like the strings accepted by `from_hex`.
+    pub fn to_hex(&self) -> Result<String> {
+        if !self.len.is_multiple_of(8) {
+            bail!("Bit length {} is not a whole number of bytes", self.len);
+        }
+
+        Ok(self
+            .data
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect())
     }
 
     pub fn get(&self, index: usize) -> bool {
@@ -28,7 +93,77 @@ pub fn set(&mut self, index: usize, value: bool) {
         }
     }
 
-    pub fn slice(&self, range: Range<usize>) -> BitMapRef {
+    pub fn push(&mut self, value: bool) {
+        if self.len == self.data.len() * 8 {
+            self.data.push(0);
+        }
+
+        self.len += 1;
+        self.set(self.len - 1, value);
+    }
+
+    pub fn pop(&mut self) -> Option<bool> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let value = self.get(self.len - 1);
+        self.truncate(self.len - 1);
+
+        Some(value)
+    }
+
+    pub fn resize(&mut self, new_len: usize, value: bool) {
+        if new_len <= self.len {
+            self.truncate(new_len);
+            return;
+        }
+
+        if !value {
+            self.data.resize(new_len.div_ceil(8), 0);
+            self.len = new_len;
+            return;
+        }
+
+        while self.len < new_len {
+            self.push(value);
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+
+        self.data.truncate(len.div_ceil(8));
+        self.len = len;
+        self.clear_unused_bits();
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.data
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn count_zeros(&self) -> usize {
+        self.len - self.count_ones()
+    }
+
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .filter(|(_, &byte)| byte != 0)
+            .flat_map(|(byte_index, &byte)| {
+                (0..8)
+                    .filter(move |bit| byte & (1 << (7 - bit)) != 0)
+                    .map(move |bit| byte_index * 8 + bit)
+            })
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> BitMapRef<'_> {
         BitMapRef {
             bitmap: self,
             start: range.start.clamp(0, self.len()),
@@ -37,15 +172,113 @@ pub fn slice(&self, range: Range<usize>) -> BitMapRef {
     }
 
     pub fn len(&self) -> usize {
-        self.data.len() * 8
+        self.len
     }
 
     pub fn is_empty(&self) -> bool {
-        self.data.is_empty()
+        self.len == 0
+    }
+
+    fn clear_unused_bits(&mut self) {
+        let used_bits = self.len % 8;
+        if used_bits != 0 {
+            *self.data.last_mut().unwrap() &= !(0xFF >> used_bits);
+        }
+    }
+
+    fn combine_with(&mut self, other: &BitMap, op: impl Fn(u8, u8) -> u8) {
+        assert_eq!(self.len, other.len);
+
+        for (byte, &other_byte) in self.data.iter_mut().zip(&other.data) {
+            *byte = op(*byte, other_byte);
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+impl BitAndAssign<&BitMap> for BitMap {
+    fn bitand_assign(&mut self, rhs: &BitMap) {
+        self.combine_with(rhs, |a, b| a & b);
+    }
+}
+
+impl BitOrAssign<&BitMap> for BitMap {
+    fn bitor_assign(&mut self, rhs: &BitMap) {
+        self.combine_with(rhs, |a, b| a | b);
+    }
+}
+
+impl BitXorAssign<&BitMap> for BitMap {
+    fn bitxor_assign(&mut self, rhs: &BitMap) {
+        self.combine_with(rhs, |a, b| a ^ b);
+    }
+}
+
+impl BitAnd<&BitMap> for &BitMap {
+    type Output = BitMap;
+
+    fn bitand(self, rhs: &BitMap) -> Self::Output {
+        let mut result = self.clone();
+        result &= rhs;
+        result
+    }
+}
+
+impl BitOr<&BitMap> for &BitMap {
+    type Output = BitMap;
+
+    fn bitor(self, rhs: &BitMap) -> Self::Output {
+        let mut result = self.clone();
+        result |= rhs;
+        result
+    }
+}
+
+impl BitXor<&BitMap> for &BitMap {
+    type Output = BitMap;
+
+    fn bitxor(self, rhs: &BitMap) -> Self::Output {
+        let mut result = self.clone();
+        result ^= rhs;
+        result
+    }
+}
+
+impl Not for BitMap {
+    type Output = BitMap;
+
+    fn not(mut self) -> Self::Output {
+        for byte in &mut self.data {
+            *byte = !*byte;
+        }
+        self.clear_unused_bits();
+
+        self
+    }
+}
+
+impl Not for &BitMap {
+    type Output = BitMap;
+
+    fn not(self) -> Self::Output {
+        !self.clone()
+    }
+}
+
+impl Display for BitMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&BitMapRef::from(self), f)
+    }
+}
+
+impl Debug for BitMap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BitMap")
+            .field(&format_args!("{}", self))
+            .finish()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct BitMapRef<'a> {
     bitmap: &'a BitMap,
     start: usize,
@@ -68,11 +301,7 @@ pub fn slice(&self, range: Range<usize>) -> Self {
     }
 
     pub fn len(&self) -> usize {
-        if self.start >= self.end {
-            0
-        } else {
-            self.end - self.start
-        }
+        self.end.saturating_sub(self.start)
     }
 
     pub fn is_empty(&self) -> bool {
@@ -80,13 +309,72 @@ pub fn is_empty(&self) -> bool {
     }
 }
 
+impl<'a> BitMapRef<'a> {
+    pub fn reader(&self) -> BitReader<'a> {
+        BitReader::new(*self)
+    }
+}
+
+impl<'a> Display for BitMapRef<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let bits: String = (0..self.len())
+            .map(|index| if self.get(index) { '1' } else { '0' })
+            .collect();
+
+        f.pad(&bits)
+    }
+}
+
+impl<'a> Debug for BitMapRef<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BitMapRef")
+            .field(&format_args!("{}", self))
+            .finish()
+    }
+}
+
 impl<'a> From<&'a BitMap> for BitMapRef<'a> {
     fn from(bitmap: &'a BitMap) -> Self {
         bitmap.slice(0..bitmap.len())
     }
 }
 
-impl<'a> TryFrom<BitMapRef<'a>> for u64 {
+macro_rules! impl_try_from_bitmap_ref {
+    ($($type:ty),*) => {
+        $(
+            impl<'a> TryFrom<BitMapRef<'a>> for $type {
+                type Error = anyhow::Error;
+
+                fn try_from(value: BitMapRef<'a>) -> Result<Self, Self::Error> {
+                    Self::try_from(&value)
+                }
+            }
+
+            impl<'a, 'b> TryFrom<&'a BitMapRef<'b>> for $type {
+                type Error = anyhow::Error;
+
+                fn try_from(value: &'a BitMapRef<'b>) -> Result<Self, Self::Error> {
+                    if value.len() > <$type>::BITS.try_into().unwrap() {
+                        bail!("Value has too many bits");
+                    }
+
+                    let mut result: $type = 0;
+
+                    for index in 0..value.len() {
+                        result <<= 1;
+                        result |= if value.get(index) { 1 } else { 0 };
+                    }
+
+                    Ok(result)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_bitmap_ref!(u8, u16, u32, u64, u128);
+
+impl<'a> TryFrom<BitMapRef<'a>> for BigUint {
     type Error = anyhow::Error;
 
     fn try_from(value: BitMapRef<'a>) -> Result<Self, Self::Error> {
@@ -94,21 +382,565 @@ fn try_from(value: BitMapRef<'a>) -> Result<Self, Self::Error> {
     }
 }
 
-impl<'a, 'b> TryFrom<&'a BitMapRef<'b>> for u64 {
+impl<'a, 'b> TryFrom<&'a BitMapRef<'b>> for BigUint {
     type Error = anyhow::Error;
 
     fn try_from(value: &'a BitMapRef<'b>) -> Result<Self, Self::Error> {
-        if value.len() > u64::BITS.try_into().unwrap() {
-            bail!("Value has too many bits");
-        }
+        let mut result = BigUint::zero();
 
-        let mut result = 0u64;
+        for start in (0..value.len()).step_by(64) {
+            let chunk = value.slice(start..start + 64);
+            let chunk_value: u64 = chunk.try_into()?;
 
-        for index in 0..value.len() {
-            result <<= 1;
-            result |= if value.get(index) { 1 } else { 0 };
+            result <<= chunk.len();
+            result |= BigUint::from(chunk_value);
         }
 
         Ok(result)
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitReader<'a> {
+    data: BitMapRef<'a>,
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: BitMapRef<'a>) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.read_slice(1)?.get(0))
+    }
+
+    pub fn read_bits(&mut self, count: usize) -> Result<u64> {
+        self.read_uint(count)
+    }
+
+    pub fn read_uint<T>(&mut self, count: usize) -> Result<T>
+    where
+        T: TryFrom<BitMapRef<'a>, Error = anyhow::Error>,
+    {
+        let value = self.peek_slice(count)?.try_into()?;
+        self.position += count;
+
+        Ok(value)
+    }
+
+    pub fn read_slice(&mut self, count: usize) -> Result<BitMapRef<'a>> {
+        let slice = self.peek_slice(count)?;
+        self.position += count;
+
+        Ok(slice)
+    }
+
+    fn peek_slice(&self, count: usize) -> Result<BitMapRef<'a>> {
+        if count > self.remaining() {
+            bail!(
+                "Cannot read {} bits at position {}, only {} remaining",
+                count,
+                self.position,
+                self.remaining()
+            );
+        }
+
+        Ok(self.data.slice(self.position..self.position + count))
+    }
+
+    /// Splits off the next `count` bits into a separate reader, and advances past them.
+    pub fn sub_reader(&mut self, count: usize) -> Result<Self> {
+        Ok(Self::new(self.read_slice(count)?))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct BitWriter {
+    bitmap: BitMap,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bitmap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bitmap.is_empty()
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.bitmap.push(value);
+    }
+
+    pub fn write_bits(&mut self, value: u64, count: usize) -> Result<()> {
+        if count > u64::BITS.try_into().unwrap() {
+            bail!("Cannot write {} bits from a u64", count);
+        }
+
+        if count < u64::BITS.try_into().unwrap() && value >> count != 0 {
+            bail!("Value {} does not fit in {} bits", value, count);
+        }
+
+        for index in (0..count).rev() {
+            self.write_bool(value & (1 << index) != 0);
+        }
+
+        Ok(())
+    }
+
+    pub fn write_slice(&mut self, bits: BitMapRef) {
+        for index in 0..bits.len() {
+            self.write_bool(bits.get(index));
+        }
+    }
+
+    pub fn pad_to_byte(&mut self) {
+        self.bitmap.resize(self.len().div_ceil(8) * 8, false);
+    }
+
+    pub fn finish(self) -> BitMap {
+        self.bitmap
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitGrid {
+    rows: Vec<BitMap>,
+    cols: usize,
+    background: bool,
+}
+
+impl BitGrid {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            rows: vec![BitMap::zeroed(cols); rows],
+            cols,
+            background: false,
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The value of every cell outside the grid, for images that extend infinitely.
+    pub fn background(&self) -> bool {
+        self.background
+    }
+
+    pub fn set_background(&mut self, background: bool) {
+        self.background = background;
+    }
+
+    pub fn get(&self, pos: &Position) -> bool {
+        assert!(pos.row < self.rows());
+        assert!(pos.col < self.cols());
+
+        self.rows[pos.row].get(pos.col)
+    }
+
+    pub fn set(&mut self, pos: &Position, value: bool) {
+        assert!(pos.row < self.rows());
+        assert!(pos.col < self.cols());
+
+        self.rows[pos.row].set(pos.col, value);
+    }
+
+    pub fn get_or_background(&self, row: isize, col: isize) -> bool {
+        match (usize::try_from(row), usize::try_from(col)) {
+            (Ok(row), Ok(col)) if row < self.rows() && col < self.cols() => {
+                self.get(&Position { row, col })
+            }
+            _ => self.background,
+        }
+    }
+
+    /// Reads the 3x3 square centered on the given cell as a 9-bit number,
+    /// row by row, with the top-left cell as the most significant bit.
+    pub fn neighbourhood_index(&self, row: isize, col: isize) -> u16 {
+        let mut index = 0;
+
+        for row in row - 1..=row + 1 {
+            for col in col - 1..=col + 1 {
+                index <<= 1;
+                index |= self.get_or_background(row, col) as u16;
+            }
+        }
+
+        index
+    }
+
+    pub fn row(&self, row: usize) -> &BitMap {
+        &self.rows[row]
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.rows.iter().map(BitMap::count_ones).sum()
+    }
+
+    pub fn iter_ones(&self) -> impl Iterator<Item = Position> + '_ {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, bits)| bits.iter_ones().map(move |col| Position { row, col }))
+    }
+
+    fn combine_with(&mut self, other: &BitGrid, op: impl Fn(&mut BitMap, &BitMap)) {
+        assert_eq!(self.rows(), other.rows());
+        assert_eq!(self.cols(), other.cols());
+
+        for (row, other_row) in self.rows.iter_mut().zip(&other.rows) {
+            op(row, other_row);
+        }
+    }
+}
+
+impl BitAndAssign<&BitGrid> for BitGrid {
+    fn bitand_assign(&mut self, rhs: &BitGrid) {
+        self.combine_with(rhs, |a, b| *a &= b);
+        self.background &= rhs.background;
+    }
+}
+
+impl BitOrAssign<&BitGrid> for BitGrid {
+    fn bitor_assign(&mut self, rhs: &BitGrid) {
+        self.combine_with(rhs, |a, b| *a |= b);
+        self.background |= rhs.background;
+    }
+}
+
+impl BitXorAssign<&BitGrid> for BitGrid {
+    fn bitxor_assign(&mut self, rhs: &BitGrid) {
+        self.combine_with(rhs, |a, b| *a ^= b);
+        self.background ^= rhs.background;
+    }
+}
+
+impl Not for BitGrid {
+    type Output = BitGrid;
+
+    fn not(self) -> Self::Output {
+        Self {
+            rows: self.rows.into_iter().map(|row| !row).collect(),
+            cols: self.cols,
+            background: !self.background,
+        }
+    }
+}
+
+impl Display for BitGrid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in &self.rows {
+            for col in 0..self.cols {
+                write!(f, "{}", if row.get(col) { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use num::BigUint;
+
+    use crate::matrix::Position;
+
+    use super::{BitGrid, BitMap, BitWriter};
+
+    fn bits(string: &str) -> BitMap {
+        BitMap::from_binary_str(string).unwrap()
+    }
+
+    #[test]
+    fn read_fields() {
+        let bitmap = BitMap::new(vec![0b1011_0010, 0b0111_1111]);
+        let mut reader = bitmap.slice(0..bitmap.len()).reader();
+
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(reader.read_bits(3).unwrap(), 0b011);
+        assert_eq!(reader.position(), 4);
+        assert_eq!(reader.read_bits(0).unwrap(), 0);
+        assert_eq!(reader.read_bits(8).unwrap(), 0b0010_0111);
+        assert_eq!(reader.remaining(), 4);
+        assert_eq!(reader.read_bits(4).unwrap(), 0b1111);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn sub_reader_is_bounded() {
+        let bitmap = BitMap::new(vec![0b1100_1010]);
+        let mut reader = bitmap.slice(0..bitmap.len()).reader();
+
+        let mut sub_reader = reader.sub_reader(3).unwrap();
+        assert_eq!(reader.position(), 3);
+        assert_eq!(sub_reader.read_bits(3).unwrap(), 0b110);
+        assert!(sub_reader.read_bool().is_err());
+
+        assert_eq!(reader.read_bits(5).unwrap(), 0b01010);
+    }
+
+    #[test]
+    fn out_of_range_reads_fail() {
+        let bitmap = BitMap::new(vec![0xFF]);
+        let mut reader = bitmap.slice(0..bitmap.len()).reader();
+
+        assert!(reader.read_bits(9).is_err());
+        assert!(reader.sub_reader(9).is_err());
+        assert_eq!(reader.position(), 0);
+
+        reader.read_bits(8).unwrap();
+        assert!(reader.read_bool().is_err());
+    }
+
+    #[test]
+    fn write_fields() {
+        let mut writer = BitWriter::new();
+
+        writer.write_bool(true);
+        writer.write_bits(0b011, 3).unwrap();
+        writer.write_bits(0b0010_0111, 8).unwrap();
+        assert_eq!(writer.len(), 12);
+
+        writer.pad_to_byte();
+        assert_eq!(writer.len(), 16);
+
+        writer.write_bits(u64::MAX, 64).unwrap();
+
+        assert_eq!(
+            writer.finish(),
+            BitMap::new(vec![
+                0b1011_0010,
+                0b0111_0000,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF,
+                0xFF
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_writes_fail() {
+        let mut writer = BitWriter::new();
+
+        assert!(writer.write_bits(0b100, 2).is_err());
+        assert!(writer.write_bits(0, 65).is_err());
+        assert!(writer.is_empty());
+    }
+
+    #[test]
+    fn round_trip() {
+        let fields = [
+            (0b1, 1),
+            (0b101, 3),
+            (0x7FF, 11),
+            (0, 15),
+            (0xDEAD_BEEF, 32),
+            (42, 64),
+        ];
+
+        let mut writer = BitWriter::new();
+        for &(value, count) in &fields {
+            writer.write_bits(value, count).unwrap();
+        }
+        let bitmap = writer.finish();
+
+        let mut reader = bitmap.slice(0..bitmap.len()).reader();
+        for &(value, count) in &fields {
+            assert_eq!(reader.read_bits(count).unwrap(), value);
+        }
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn string_conversions() {
+        let bitmap = BitMap::from_hex("8a0F").unwrap();
+        assert_eq!(bitmap, BitMap::new(vec![0x8A, 0x0F]));
+        assert_eq!(bitmap.to_hex().unwrap(), "8A0F");
+        assert_eq!(BitMap::from_hex(&bitmap.to_hex().unwrap()).unwrap(), bitmap);
+        assert_eq!(bitmap.to_string(), "1000101000001111");
+        assert_eq!(bitmap.slice(4..9).to_string(), "10100");
+        assert_eq!(format!("{:?}", bitmap), "BitMap(1000101000001111)");
+
+        assert_eq!(BitMap::from_binary_str("1000101000001111").unwrap(), bitmap);
+        assert_eq!(BitMap::from_hex("").unwrap(), BitMap::new(vec![]));
+    }
+
+    #[test]
+    fn invalid_strings_fail() {
+        assert!(BitMap::from_hex("8A0").is_err());
+        assert!(BitMap::from_hex("8G").is_err());
+        assert!(BitMap::from_hex("é").is_err());
+        assert!(BitMap::from_binary_str("01010102").is_err());
+    }
+
+    #[test]
+    fn exact_lengths() {
+        let mut bitmap = bits("1011001");
+        assert_eq!(bitmap.len(), 7);
+        assert_eq!(bitmap.to_string(), "1011001");
+        assert!(bitmap.to_hex().is_err());
+        assert!(bits("1011").to_hex().is_err());
+
+        bitmap.resize(13, true);
+        assert_eq!(bitmap, bits("1011001111111"));
+
+        bitmap.truncate(5);
+        assert_eq!(bitmap, bits("10110"));
+        assert_eq!(bitmap.pop(), Some(false));
+
+        bitmap.resize(6, false);
+        assert_eq!(bitmap, bits("101100"));
+        assert_eq!(BitMap::zeroed(3), bits("000"));
+        assert_eq!(BitMap::default().pop(), None);
+    }
+
+    #[test]
+    fn bitwise_algebra() {
+        let a = bits("1100110011");
+        let b = bits("1010101010");
+
+        assert_eq!(&a & &b, bits("1000100010"));
+        assert_eq!(&a | &b, bits("1110111011"));
+        assert_eq!(&a ^ &b, bits("0110011001"));
+        assert_eq!(!&a, bits("0011001100"));
+        assert_eq!(!!a.clone(), a);
+
+        assert_eq!(a.count_ones(), 6);
+        assert_eq!(a.count_zeros(), 4);
+        assert_eq!(a.iter_ones().collect::<Vec<_>>(), [0, 1, 4, 5, 8, 9]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn bitwise_algebra_needs_equal_lengths() {
+        let _ = &bits("101") & &bits("1010");
+    }
+
+    #[test]
+    fn wide_integers() {
+        let bitmap = BitMap::from_hex("0123456789ABCDEF0123456789ABCDEF0123").unwrap();
+        let all_bits = bitmap.slice(0..bitmap.len());
+
+        assert!(u64::try_from(all_bits).is_err());
+        assert!(u128::try_from(all_bits).is_err());
+        assert_eq!(
+            u128::try_from(all_bits.slice(4..132)).unwrap(),
+            0x123456789ABCDEF0123456789ABCDEF0
+        );
+        assert_eq!(
+            BigUint::try_from(all_bits).unwrap(),
+            BigUint::parse_bytes(b"0123456789ABCDEF0123456789ABCDEF0123", 16).unwrap()
+        );
+        assert_eq!(
+            BigUint::try_from(all_bits.slice(0..0)).unwrap(),
+            BigUint::from(0u8)
+        );
+
+        let mut reader = all_bits.reader();
+        assert_eq!(reader.read_uint::<u8>(8).unwrap(), 0x01);
+        assert!(reader.read_uint::<u8>(9).is_err());
+        assert_eq!(reader.position(), 8);
+        assert_eq!(reader.read_uint::<u16>(12).unwrap(), 0x234);
+    }
+
+    #[test]
+    fn grid_cells() {
+        let mut grid = BitGrid::new(3, 13);
+        grid.set(&Position { row: 0, col: 12 }, true);
+        grid.set(&Position { row: 2, col: 0 }, true);
+        grid.set(&Position { row: 2, col: 1 }, true);
+
+        assert!(grid.get(&Position { row: 0, col: 12 }));
+        assert!(!grid.get(&Position { row: 1, col: 12 }));
+        assert_eq!(grid.row(2), &bits("1100000000000"));
+        assert_eq!(grid.count_ones(), 3);
+        assert_eq!(
+            grid.iter_ones().collect::<Vec<_>>(),
+            [
+                Position { row: 0, col: 12 },
+                Position { row: 2, col: 0 },
+                Position { row: 2, col: 1 }
+            ]
+        );
+        assert_eq!(
+            grid.to_string(),
+            "............#\n.............\n##...........\n"
+        );
+    }
+
+    #[test]
+    fn grid_neighbourhoods() {
+        let mut grid = BitGrid::new(3, 3);
+        grid.set(&Position { row: 0, col: 0 }, true);
+        grid.set(&Position { row: 1, col: 1 }, true);
+        grid.set(&Position { row: 2, col: 2 }, true);
+
+        assert_eq!(grid.neighbourhood_index(1, 1), 0b100_010_001);
+        assert_eq!(grid.neighbourhood_index(0, 0), 0b000_010_001);
+        assert_eq!(grid.neighbourhood_index(-1, -1), 0b000_000_001);
+        assert_eq!(grid.neighbourhood_index(5, 5), 0);
+
+        grid.set_background(true);
+        assert_eq!(grid.neighbourhood_index(0, 0), 0b111_110_101);
+        assert_eq!(grid.neighbourhood_index(5, 5), 0b111_111_111);
+
+        let grid = !grid;
+        assert!(!grid.background());
+        assert_eq!(grid.neighbourhood_index(1, 1), 0b011_101_110);
+    }
+
+    #[test]
+    fn grid_algebra() {
+        let mut a = BitGrid::new(2, 2);
+        a.set(&Position { row: 0, col: 0 }, true);
+        a.set(&Position { row: 1, col: 1 }, true);
+
+        let mut b = BitGrid::new(2, 2);
+        b.set(&Position { row: 0, col: 0 }, true);
+        b.set(&Position { row: 0, col: 1 }, true);
+        b.set_background(true);
+
+        let mut and = a.clone();
+        and &= &b;
+        assert_eq!(and.to_string(), "#.\n..\n");
+        assert!(!and.background());
+
+        let mut or = a.clone();
+        or |= &b;
+        assert_eq!(or.to_string(), "##\n.#\n");
+        assert!(or.background());
+
+        let mut xor = a;
+        xor ^= &b;
+        assert_eq!(xor.to_string(), ".#\n.#\n");
+        assert!(xor.background());
+    }
+}
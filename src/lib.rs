@@ -2,4 +2,6 @@
 pub mod bitmap;
 pub mod geometry;
 pub mod matrix;
+pub mod sonar;
+pub mod submarine;
 pub mod util;
@@ -118,7 +118,7 @@ fn hash<H: Hasher>(&self, state: &mut H) {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Position {
     pub row: usize,
     pub col: usize,
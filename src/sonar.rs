@@ -0,0 +1,109 @@
+use std::{
+    collections::VecDeque,
+    ops::{Add, Sub},
+};
+
+/// Counts how many sums of `window` consecutive values are larger than the
+/// sum of the preceding window. Consecutive windows share all but one value,
+/// so only the values entering and leaving the window need to be compared.
+pub fn count_window_increases<I>(values: I, window: usize) -> usize
+where
+    I: IntoIterator,
+    I::Item: Ord,
+{
+    assert!(window > 0);
+
+    let mut buffer = VecDeque::with_capacity(window + 1);
+    let mut increases = 0;
+
+    for value in values {
+        buffer.push_back(value);
+
+        if buffer.len() > window {
+            let leaving = buffer.pop_front().unwrap();
+            if buffer.back().unwrap() > &leaving {
+                increases += 1;
+            }
+        }
+    }
+
+    increases
+}
+
+/// Yields the sums of every `window` consecutive values.
+pub fn window_sums<I>(values: I, window: usize) -> WindowSums<I::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: Copy + Add<Output = I::Item> + Sub<Output = I::Item>,
+{
+    assert!(window > 0);
+
+    WindowSums {
+        values: values.into_iter(),
+        window,
+        buffer: VecDeque::with_capacity(window),
+        sum: None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowSums<I: Iterator> {
+    values: I,
+    window: usize,
+    buffer: VecDeque<I::Item>,
+    sum: Option<I::Item>,
+}
+
+impl<I> Iterator for WindowSums<I>
+where
+    I: Iterator,
+    I::Item: Copy + Add<Output = I::Item> + Sub<Output = I::Item>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let value = self.values.next()?;
+
+            self.buffer.push_back(value);
+            self.sum = Some(match self.sum {
+                Some(sum) => sum + value,
+                None => value,
+            });
+
+            if self.buffer.len() > self.window {
+                let leaving = self.buffer.pop_front().unwrap();
+                self.sum = Some(self.sum.unwrap() - leaving);
+            }
+
+            if self.buffer.len() == self.window {
+                return self.sum;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_window_increases, window_sums};
+
+    const DEPTHS: [u32; 10] = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+
+    #[test]
+    fn window_increases() {
+        assert_eq!(count_window_increases(DEPTHS, 1), 7);
+        assert_eq!(count_window_increases(DEPTHS, 3), 5);
+        assert_eq!(count_window_increases(DEPTHS, 10), 0);
+        assert_eq!(count_window_increases(DEPTHS, 20), 0);
+        assert_eq!(count_window_increases(["a", "c", "b", "d"], 1), 2);
+    }
+
+    #[test]
+    fn rolling_sums() {
+        let sums: Vec<u32> = window_sums(DEPTHS, 3).collect();
+        assert_eq!(sums, [607, 618, 618, 617, 647, 716, 769, 792]);
+        assert_eq!(count_window_increases(sums, 1), 5);
+
+        assert_eq!(window_sums(DEPTHS, 11).count(), 0);
+    }
+}
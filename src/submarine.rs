@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Command {
+    Forward(i64),
+    Down(i64),
+    Up(i64),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SubmarineState {
+    pub horizontal_pos: i64,
+    pub depth: i64,
+    pub aim: i64,
+}
+
+pub trait MovementModel {
+    fn apply(&self, state: &mut SubmarineState, command: Command);
+}
+
+/// `down` and `up` change the depth directly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct SimpleModel;
+
+impl MovementModel for SimpleModel {
+    fn apply(&self, state: &mut SubmarineState, command: Command) {
+        match command {
+            Command::Forward(x) => state.horizontal_pos += x,
+            Command::Down(x) => state.depth += x,
+            Command::Up(x) => state.depth -= x,
+        }
+    }
+}
+
+/// `down` and `up` change the aim, and moving forward dives along it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct AimModel;
+
+impl MovementModel for AimModel {
+    fn apply(&self, state: &mut SubmarineState, command: Command) {
+        match command {
+            Command::Down(x) => state.aim += x,
+            Command::Up(x) => state.aim -= x,
+            Command::Forward(x) => {
+                state.horizontal_pos += x;
+                state.depth += state.aim * x;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Submarine<M: MovementModel> {
+    model: M,
+    state: SubmarineState,
+}
+
+impl<M: MovementModel> Submarine<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            state: SubmarineState::default(),
+        }
+    }
+
+    pub fn state(&self) -> SubmarineState {
+        self.state
+    }
+
+    pub fn execute(&mut self, command: Command) {
+        self.model.apply(&mut self.state, command);
+    }
+
+    pub fn execute_all(&mut self, commands: impl IntoIterator<Item = Command>) {
+        for command in commands {
+            self.execute(command);
+        }
+    }
+
+    /// Executes the commands lazily, yielding the state after every step.
+    pub fn trace<'a, I>(&'a mut self, commands: I) -> impl Iterator<Item = SubmarineState> + 'a
+    where
+        I: IntoIterator<Item = Command>,
+        I::IntoIter: 'a,
+    {
+        commands.into_iter().map(move |command| {
+            self.execute(command);
+            self.state
+        })
+    }
+}
+
+/// Maps the words of a course file to commands.
+#[derive(Default)]
+pub struct CommandSet {
+    words: HashMap<String, Box<dyn Fn(i64) -> Command>>,
+}
+
+impl CommandSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn standard() -> Self {
+        let mut commands = Self::new();
+        commands.register("forward", Command::Forward);
+        commands.register("down", Command::Down);
+        commands.register("up", Command::Up);
+        commands
+    }
+
+    pub fn register(&mut self, word: &str, command: impl Fn(i64) -> Command + 'static) {
+        self.words.insert(word.to_owned(), Box::new(command));
+    }
+
+    pub fn parse(&self, line: &str) -> Result<Command> {
+        let mut parts = line.split_whitespace();
+
+        let word = parts
+            .next()
+            .with_context(|| format!("No direction in line '{}'", line))?;
+        let count: i64 = parts
+            .next()
+            .with_context(|| format!("No count in line '{}'", line))?
+            .parse()
+            .with_context(|| format!("Failed parsing count in line '{}'", line))?;
+
+        if parts.next().is_some() {
+            bail!("Trailing data in line '{}'", line);
+        }
+
+        let command = self
+            .words
+            .get(word)
+            .with_context(|| format!("Invalid direction '{}'", word))?;
+
+        Ok(command(count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AimModel, Command, CommandSet, SimpleModel, Submarine, SubmarineState};
+
+    const COURSE: [Command; 6] = [
+        Command::Forward(5),
+        Command::Down(5),
+        Command::Forward(8),
+        Command::Up(3),
+        Command::Down(8),
+        Command::Forward(2),
+    ];
+
+    #[test]
+    fn movement_models() {
+        let mut submarine = Submarine::new(SimpleModel);
+        submarine.execute_all(COURSE);
+        assert_eq!(submarine.state().horizontal_pos, 15);
+        assert_eq!(submarine.state().depth, 10);
+
+        let mut submarine = Submarine::new(AimModel);
+        submarine.execute_all(COURSE);
+        assert_eq!(submarine.state().horizontal_pos, 15);
+        assert_eq!(submarine.state().depth, 60);
+    }
+
+    #[test]
+    fn surfacing_goes_negative() {
+        let mut submarine = Submarine::new(SimpleModel);
+        let trace: Vec<i64> = submarine
+            .trace([Command::Down(1), Command::Up(3)])
+            .map(|state| state.depth)
+            .collect();
+
+        assert_eq!(trace, [1, -2]);
+        assert_eq!(
+            submarine.state(),
+            SubmarineState {
+                horizontal_pos: 0,
+                depth: -2,
+                aim: 0
+            }
+        );
+    }
+
+    #[test]
+    fn custom_commands() {
+        let mut commands = CommandSet::standard();
+        commands.register("backward", |x| Command::Forward(-x));
+
+        assert_eq!(commands.parse("backward 4").unwrap(), Command::Forward(-4));
+        assert_eq!(commands.parse("up 2").unwrap(), Command::Up(2));
+        assert!(commands.parse("sideways 2").is_err());
+        assert!(commands.parse("up").is_err());
+        assert!(commands.parse("up 2 3").is_err());
+    }
+}
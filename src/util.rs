@@ -2,7 +2,7 @@
     env,
     ffi::OsStr,
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Read},
     path::PathBuf,
 };
 
@@ -36,3 +36,9 @@ pub fn input_file() -> Result<File> {
 pub fn input_lines() -> Result<impl Iterator<Item = Result<String>>> {
     Ok(BufReader::new(input_file()?).lines().map(|line| Ok(line?)))
 }
+
+pub fn input_string() -> Result<String> {
+    let mut string = String::new();
+    input_file()?.read_to_string(&mut string)?;
+    Ok(string)
+}